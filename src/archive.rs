@@ -0,0 +1,536 @@
+use std::{
+    fs::File,
+    io::Read,
+    path::{Component, Path, PathBuf},
+};
+
+use flate2::read::GzDecoder;
+use tempfile::TempDir;
+
+use crate::{
+    error::{CunwError, CunwErrorKind, Result},
+    logger::Logger,
+    utils,
+};
+
+/// The default of `--max-archive-size`, the most bytes the files of an archive may
+/// extract to.
+pub const DEFAULT_MAX_EXTRACTED_SIZE: u64 = 1_000_000_000;
+
+/// The kind of an archive, told apart by its extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveKind {
+    /// A `.zip` archive.
+    Zip,
+    /// An uncompressed `.tar` archive.
+    Tar,
+    /// A gzip compressed `.tar.gz` or `.tgz` archive.
+    TarGz,
+}
+
+impl ArchiveKind {
+    /// Returns the kind of the archive at `path` from its extension, or [`None`] if it
+    /// is not an archive.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_string_lossy().to_lowercase();
+        if name.ends_with(".zip") {
+            Some(Self::Zip)
+        } else if name.ends_with(".tar") {
+            Some(Self::Tar)
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(Self::TarGz)
+        } else {
+            None
+        }
+    }
+    /// Returns the name of the archive at `path` without its extension,
+    /// e.g. `project` for `project.tar.gz`.
+    fn stem(self, path: &Path) -> String {
+        let name = path
+            .file_name()
+            .map_or_else(String::new, |name| name.to_string_lossy().into_owned());
+        let extension_len = match self {
+            Self::Zip => ".zip".len(),
+            Self::Tar => ".tar".len(),
+            Self::TarGz if name.to_lowercase().ends_with(".tgz") => ".tgz".len(),
+            Self::TarGz => ".tar.gz".len(),
+        };
+        name[..name.len() - extension_len].to_string()
+    }
+}
+
+/// An archive extracted to a temporary directory, which is walked like any other
+/// directory. The directory is removed when this is dropped.
+#[derive(Debug)]
+pub struct ExtractedArchive {
+    /// Kept so that the directory lives as long as the extracted archive.
+    _dir: TempDir,
+    root: PathBuf,
+}
+
+impl ExtractedArchive {
+    /// Extracts the archive at `path` to a temporary directory.
+    ///
+    /// The entries are extracted in a directory named after the archive, so that the
+    /// root of the directory tree is named like it. If the archive holds a single
+    /// directory (e.g. `project/` in `project-v1.tar.gz`), that directory is the root.
+    ///
+    /// `is_excluded` is given the path of each entry relative to that root, and
+    /// whether it is a directory. The content of the excluded entries is not extracted:
+    /// an excluded file is left empty and an excluded directory is left without
+    /// children, so that the walk still reports them as excluded.
+    ///
+    /// The entries whose path would escape the temporary directory (e.g. through `..`
+    /// components) are skipped, as are the symbolic links, which could point outside
+    /// of it.
+    ///
+    /// **Errors**
+    ///
+    /// If the files of the archive extract to more than `max_size` bytes.
+    pub fn extract(
+        path: &Path,
+        kind: ArchiveKind,
+        max_size: u64,
+        is_excluded: impl Fn(&Path, bool) -> bool,
+    ) -> Result<Self> {
+        Logger::debug(format!("Extracting the archive {}", path.display()).as_str());
+        let archive_error = |err: String| {
+            CunwError::new(CunwErrorKind::Archive(path.to_path_buf(), err))
+                .with_file(path.to_path_buf())
+        };
+        let open = || {
+            File::open(path).map_err(|err| CunwError::new(err.into()).with_file(path.to_path_buf()))
+        };
+        let open_tar = || -> Result<tar::Archive<Box<dyn Read>>> {
+            let file = open()?;
+            Ok(tar::Archive::new(match kind {
+                ArchiveKind::TarGz => Box::new(GzDecoder::new(file)),
+                _ => Box::new(file),
+            }))
+        };
+        let dir = TempDir::new().map_err(|err| CunwError::new(err.into()))?;
+        let mut extractor = Extractor {
+            archive_root: dir.path().join(kind.stem(path)),
+            prefix: None,
+            remaining: max_size,
+            max_size,
+            is_excluded,
+        };
+
+        // The entries are listed first, to match the patterns against the paths
+        // relative to the root before anything is written
+        match kind {
+            ArchiveKind::Zip => {
+                let mut archive =
+                    zip::ZipArchive::new(open()?).map_err(|err| archive_error(err.to_string()))?;
+                let mut entries = Vec::new();
+                for index in 0..archive.len() {
+                    let file = archive
+                        .by_index(index)
+                        .map_err(|err| archive_error(err.to_string()))?;
+                    if let Some(entry_path) = zip_entry_path(&file) {
+                        entries.push((entry_path, file.is_dir()));
+                    }
+                }
+                extractor.prefix = single_directory(&entries);
+                for index in 0..archive.len() {
+                    let mut file = archive
+                        .by_index(index)
+                        .map_err(|err| archive_error(err.to_string()))?;
+                    if let Some(entry_path) = zip_entry_path(&file) {
+                        let is_dir = file.is_dir();
+                        extractor
+                            .unpack(&entry_path, is_dir, &mut file)
+                            .map_err(|err| err.or_archive_error(archive_error))?;
+                    }
+                }
+            }
+            ArchiveKind::Tar | ArchiveKind::TarGz => {
+                let mut entries = Vec::new();
+                let mut archive = open_tar()?;
+                for entry in archive
+                    .entries()
+                    .map_err(|err| archive_error(err.to_string()))?
+                {
+                    let entry = entry.map_err(|err| archive_error(err.to_string()))?;
+                    if let Some(entry) = tar_entry_path(&entry) {
+                        entries.push(entry);
+                    }
+                }
+                extractor.prefix = single_directory(&entries);
+                let mut archive = open_tar()?;
+                for entry in archive
+                    .entries()
+                    .map_err(|err| archive_error(err.to_string()))?
+                {
+                    let mut entry = entry.map_err(|err| archive_error(err.to_string()))?;
+                    if let Some((entry_path, is_dir)) = tar_entry_path(&entry) {
+                        extractor
+                            .unpack(&entry_path, is_dir, &mut entry)
+                            .map_err(|err| err.or_archive_error(archive_error))?;
+                    }
+                }
+            }
+        }
+        // An empty archive creates no directory at all
+        std::fs::create_dir_all(&extractor.archive_root)
+            .map_err(|err| CunwError::new(err.into()))?;
+
+        let root = match &extractor.prefix {
+            Some(prefix) => extractor.archive_root.join(prefix),
+            None => extractor.archive_root,
+        };
+        Ok(Self { _dir: dir, root })
+    }
+    /// Returns the directory to walk.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+}
+
+/// Writes the entries of an archive, in order.
+struct Extractor<F> {
+    /// The directory named after the archive, in which the entries are written.
+    archive_root: PathBuf,
+    /// The single directory of the archive, if it holds one.
+    prefix: Option<PathBuf>,
+    /// The number of bytes that may still be written.
+    remaining: u64,
+    max_size: u64,
+    is_excluded: F,
+}
+
+/// The error of an entry that could not be written.
+enum UnpackError {
+    /// The archive could not be read, or extracts to too many bytes.
+    Archive(String),
+    /// The entry could not be written to the temporary directory.
+    Write(CunwError),
+}
+
+impl UnpackError {
+    fn or_archive_error(self, archive_error: impl Fn(String) -> CunwError) -> CunwError {
+        match self {
+            Self::Archive(err) => archive_error(err),
+            Self::Write(err) => err,
+        }
+    }
+}
+
+impl<F: Fn(&Path, bool) -> bool> Extractor<F> {
+    /// Writes the entry at `entry_path` (relative to the archive), reading the content
+    /// of a file from `reader`.
+    fn unpack(
+        &mut self,
+        entry_path: &Path,
+        is_dir: bool,
+        reader: &mut impl Read,
+    ) -> std::result::Result<(), UnpackError> {
+        let write_error = |err: std::io::Error, path: &Path| {
+            UnpackError::Write(CunwError::new(err.into()).with_file(path.to_path_buf()))
+        };
+        // Every entry is under the single directory, if there is one
+        let (root, relative_path) = match &self.prefix {
+            Some(prefix) => (
+                self.archive_root.join(prefix),
+                entry_path.strip_prefix(prefix).unwrap_or(entry_path),
+            ),
+            None => (self.archive_root.clone(), entry_path),
+        };
+
+        // The entries under an excluded directory are never written
+        let excluded_ancestor = relative_path
+            .ancestors()
+            .skip(1)
+            .filter(|ancestor| !ancestor.as_os_str().is_empty())
+            .find(|ancestor| (self.is_excluded)(ancestor, true));
+        if let Some(ancestor) = excluded_ancestor {
+            let ancestor = root.join(ancestor);
+            return std::fs::create_dir_all(&ancestor).map_err(|err| write_error(err, &ancestor));
+        }
+
+        let target = self.archive_root.join(entry_path);
+        if is_dir {
+            return std::fs::create_dir_all(&target).map_err(|err| write_error(err, &target));
+        }
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent).map_err(|err| write_error(err, parent))?;
+        }
+        let mut file = File::create(&target).map_err(|err| write_error(err, &target))?;
+        if !(self.is_excluded)(relative_path, false) {
+            // Read one byte more than allowed, to tell whether the limit is exceeded
+            let written = std::io::copy(&mut reader.take(self.remaining + 1), &mut file)
+                .map_err(|err| UnpackError::Archive(err.to_string()))?;
+            if written > self.remaining {
+                return Err(UnpackError::Archive(format!(
+                    "its files extract to more than {}, see --max-archive-size",
+                    utils::format_size(self.max_size)
+                )));
+            }
+            self.remaining -= written;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the path of `path` once its `.` and `..` components are resolved, or
+/// [`None`] if it escapes the directory it is in. A leading `/` is ignored, like
+/// `tar` does.
+fn enclosed_path(path: &Path) -> Option<PathBuf> {
+    let mut enclosed = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(name) => enclosed.push(name),
+            Component::ParentDir => {
+                if !enclosed.pop() {
+                    return None;
+                }
+            }
+            Component::CurDir | Component::RootDir | Component::Prefix(_) => {}
+        }
+    }
+    (!enclosed.as_os_str().is_empty()).then_some(enclosed)
+}
+
+/// Returns the path of a file or directory of a zip archive, or [`None`] if it is
+/// skipped.
+fn zip_entry_path<R: Read>(file: &zip::read::ZipFile<'_, R>) -> Option<PathBuf> {
+    let name = file.name().ok()?;
+    let entry_path = (!file.is_symlink())
+        .then(|| enclosed_path(Path::new(name.as_ref())))
+        .flatten();
+    if entry_path.is_none() {
+        Logger::debug(format!("Skipping the archive entry {}", name).as_str());
+    }
+    entry_path
+}
+
+/// Returns the path of a file or directory of a tar archive and whether it is a
+/// directory, or [`None`] if it is skipped.
+fn tar_entry_path<R: Read>(entry: &tar::Entry<'_, R>) -> Option<(PathBuf, bool)> {
+    let entry_type = entry.header().entry_type();
+    let entry_path = entry.path().ok()?;
+    let enclosed = (entry_type.is_file() || entry_type.is_dir())
+        .then(|| enclosed_path(&entry_path))
+        .flatten();
+    if enclosed.is_none() {
+        Logger::debug(format!("Skipping the archive entry {}", entry_path.display()).as_str());
+    }
+    enclosed.map(|path| (path, entry_type.is_dir()))
+}
+
+/// Returns the directory holding all the `entries` of an archive, if there is one.
+fn single_directory(entries: &[(PathBuf, bool)]) -> Option<PathBuf> {
+    let first_component = |path: &Path| {
+        path.components()
+            .next()
+            .map(|c| PathBuf::from(c.as_os_str()))
+    };
+    let prefix = first_component(&entries.first()?.0)?;
+    entries
+        .iter()
+        .all(|(path, is_dir)| {
+            first_component(path).as_ref() == Some(&prefix) && (*is_dir || path != &prefix)
+        })
+        .then_some(prefix)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::{write::GzEncoder, Compression};
+    use zip::{write::SimpleFileOptions, ZipWriter};
+
+    use super::*;
+
+    #[test]
+    fn test_archive_kind_from_path() {
+        assert_eq!(
+            ArchiveKind::from_path(Path::new("a/project.zip")),
+            Some(ArchiveKind::Zip)
+        );
+        assert_eq!(
+            ArchiveKind::from_path(Path::new("project.TAR")),
+            Some(ArchiveKind::Tar)
+        );
+        assert_eq!(
+            ArchiveKind::from_path(Path::new("project.tar.gz")),
+            Some(ArchiveKind::TarGz)
+        );
+        assert_eq!(
+            ArchiveKind::from_path(Path::new("project.tgz")),
+            Some(ArchiveKind::TarGz)
+        );
+        assert_eq!(ArchiveKind::from_path(Path::new("project")), None);
+        assert_eq!(
+            ArchiveKind::TarGz.stem(Path::new("project.tar.gz")),
+            "project"
+        );
+        assert_eq!(ArchiveKind::TarGz.stem(Path::new("project.tgz")), "project");
+    }
+
+    #[test]
+    fn test_extract_zip() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("project.zip");
+        let mut writer = ZipWriter::new(File::create(&path).unwrap());
+        writer
+            .start_file("src/main.rs", SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(b"fn main() {}").unwrap();
+        writer
+            .start_file(".gitignore", SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(b"*.log").unwrap();
+        writer.finish().unwrap();
+
+        let extracted = ExtractedArchive::extract(
+            &path,
+            ArchiveKind::Zip,
+            DEFAULT_MAX_EXTRACTED_SIZE,
+            |_, _| false,
+        )
+        .unwrap();
+        assert!(extracted.root().ends_with("project"));
+        assert_eq!(
+            std::fs::read_to_string(extracted.root().join("src/main.rs")).unwrap(),
+            "fn main() {}"
+        );
+        assert!(extracted.root().join(".gitignore").is_file());
+    }
+
+    #[test]
+    fn test_extract_tar_gz_with_a_single_directory() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("project-v1.tar.gz");
+        let mut builder = tar::Builder::new(GzEncoder::new(
+            File::create(&path).unwrap(),
+            Compression::default(),
+        ));
+        let content = b"fn main() {}";
+        let mut header = tar::Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, "project/src/main.rs", &content[..])
+            .unwrap();
+        builder.into_inner().unwrap().finish().unwrap();
+
+        let extracted = ExtractedArchive::extract(
+            &path,
+            ArchiveKind::TarGz,
+            DEFAULT_MAX_EXTRACTED_SIZE,
+            |_, _| false,
+        )
+        .unwrap();
+        assert!(extracted.root().ends_with("project-v1/project"));
+        assert!(extracted.root().join("src/main.rs").is_file());
+    }
+
+    /// Appends a file to a tar archive, without checking its path like
+    /// [`tar::Builder::append_data`] does.
+    fn append_raw<W: Write>(builder: &mut tar::Builder<W>, path: &str, content: &[u8]) {
+        let mut header = tar::Header::new_gnu();
+        header.as_gnu_mut().unwrap().name[..path.len()].copy_from_slice(path.as_bytes());
+        header.set_size(content.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append(&header, content).unwrap();
+    }
+
+    #[test]
+    fn test_extract_entries_stay_inside_the_temporary_directory() {
+        let dir = TempDir::new().unwrap();
+        let escaped_name = format!(
+            "{}-escaped.txt",
+            dir.path().file_name().unwrap().to_string_lossy()
+        );
+        let path = dir.path().join("project.tar");
+        let mut builder = tar::Builder::new(File::create(&path).unwrap());
+        append_raw(&mut builder, &format!("../../{}", escaped_name), b"escaped");
+        append_raw(
+            &mut builder,
+            &format!("src/../../../{}", escaped_name),
+            b"escaped",
+        );
+        append_raw(&mut builder, "/src/main.rs", b"fn main() {}");
+        append_raw(&mut builder, "src/../lib.rs", b"");
+        builder.finish().unwrap();
+
+        let extracted = ExtractedArchive::extract(
+            &path,
+            ArchiveKind::Tar,
+            DEFAULT_MAX_EXTRACTED_SIZE,
+            |_, _| false,
+        )
+        .unwrap();
+        assert!(extracted.root().ends_with("project"));
+        assert!(extracted.root().join("src/main.rs").is_file());
+        assert!(extracted.root().join("lib.rs").is_file());
+        for ancestor in extracted.root().ancestors().take(3) {
+            assert!(!ancestor.join(&escaped_name).exists());
+        }
+    }
+
+    #[test]
+    fn test_extract_skips_the_content_of_excluded_entries() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("project.tar");
+        let mut builder = tar::Builder::new(File::create(&path).unwrap());
+        append_raw(&mut builder, "project/node_modules/dep/index.js", b"module");
+        append_raw(&mut builder, "project/debug.log", b"log");
+        append_raw(&mut builder, "project/src/main.rs", b"fn main() {}");
+        builder.finish().unwrap();
+
+        let extracted = ExtractedArchive::extract(
+            &path,
+            ArchiveKind::Tar,
+            DEFAULT_MAX_EXTRACTED_SIZE,
+            |relative_path, is_dir| {
+                if is_dir {
+                    relative_path == Path::new("node_modules")
+                } else {
+                    relative_path.extension().is_some_and(|ext| ext == "log")
+                }
+            },
+        )
+        .unwrap();
+        let root = extracted.root();
+        assert!(root.ends_with("project/project"));
+        // The excluded entries are kept empty, for the walk to report them
+        assert!(root.join("node_modules").is_dir());
+        assert!(!root.join("node_modules/dep").exists());
+        assert_eq!(std::fs::read(root.join("debug.log")).unwrap(), b"");
+        assert_eq!(
+            std::fs::read(root.join("src/main.rs")).unwrap(),
+            b"fn main() {}"
+        );
+    }
+
+    #[test]
+    fn test_extract_fails_over_the_maximum_size() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("project.zip");
+        let mut writer = ZipWriter::new(File::create(&path).unwrap());
+        for name in ["a.txt", "b.txt"] {
+            writer
+                .start_file(name, SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(&[b'a'; 10]).unwrap();
+        }
+        writer.finish().unwrap();
+
+        assert!(ExtractedArchive::extract(&path, ArchiveKind::Zip, 20, |_, _| false).is_ok());
+        let err = ExtractedArchive::extract(&path, ArchiveKind::Zip, 19, |_, _| false).unwrap_err();
+        assert!(matches!(err.source, CunwErrorKind::Archive(..)));
+        // The excluded files do not count
+        assert!(
+            ExtractedArchive::extract(&path, ArchiveKind::Zip, 10, |relative_path, _| {
+                relative_path == Path::new("b.txt")
+            })
+            .is_ok()
+        );
+    }
+}
@@ -1,18 +1,114 @@
-use std::path::PathBuf;
+use std::{path::PathBuf, time::Duration};
 
-use clap::{builder::ValueHint, ArgAction, Parser};
-use clap_verbosity_flag::{InfoLevel, Verbosity};
+use clap::{
+    builder::{RangedU64ValueParser, ValueHint},
+    ArgAction, Parser,
+};
 use globset::Glob;
+use log::LevelFilter;
+
+#[cfg(feature = "signatures")]
+use crate::signatures::SignaturesFallback;
+use crate::{
+    codebase::{
+        format::{OutputFormat, PathStyle},
+        item::{BinaryMode, TextEncoding},
+        Precedence,
+    },
+    logger::LogFormat,
+    presets::Preset,
+    tree::{StableSort, TreeOrder},
+    utils,
+};
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 pub struct Args {
-    #[arg(index = 1, help = "The path to the directory containing the codebase.", value_hint = ValueHint::DirPath, required = true)]
+    #[arg(index = 1, help = "The path to the directory containing the codebase, or to a .zip, .tar, .tar.gz or .tgz archive of it.", value_hint = ValueHint::AnyPath, required = true)]
     pub path: PathBuf,
-    #[arg(short, long, help = "The path of the output file.", value_hint = ValueHint::FilePath, required = false, default_value = "output.txt")]
+    #[arg(long, help = "The path of a config file holding default values for the options (defaults to the '.cunw.toml' of the scanned directory, if any).", value_hint = ValueHint::FilePath, required = false)]
+    pub config: Option<PathBuf>,
+    #[arg(
+        long,
+        help = "Print the effective configuration (config file merged with the command line) as TOML and exit.",
+        required = false,
+        default_value = "false"
+    )]
+    pub print_config: bool,
+    #[arg(short, long, help = "The path of the output file, or '-' to write to stdout. Defaults to $CUNW_OUTPUT if set, or else to 'output.txt' (see --name-output-after-dir).", value_hint = ValueHint::FilePath, required = false)]
     pub output: Option<PathBuf>,
-    #[arg(short, long, help = "Exclude files or directories matching the specified pattern.", value_hint = ValueHint::Other, required = false, num_args = 0.., action = ArgAction::Append)]
+    #[arg(
+        long,
+        help = "When no output path is given, name the output after the scanned directory ('<dirname>.cunw.txt') instead of 'output.txt', so that outputs of different projects do not collide.",
+        required = false,
+        default_value = "false"
+    )]
+    pub name_output_after_dir: bool,
+    #[arg(long, help = "Write the content of each file to the same relative path in the specified directory (creating it if needed), along with the directory tree in 'tree.txt', instead of writing a single output file.", value_hint = ValueHint::DirPath, required = false, conflicts_with_all = ["output", "chunk_tokens"])]
+    pub output_dir: Option<PathBuf>,
+    #[arg(long, help = "The format of the output.", required = false, value_enum, default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
+    #[arg(
+        long,
+        help = "Indent the JSON output for human inspection, instead of writing it compactly on a single line.",
+        required = false,
+        default_value = "false"
+    )]
+    pub json_pretty: bool,
+    #[arg(long, help = "Split the output into parts (output.part1.txt, output.part2.txt, ...) of at most the specified number of estimated tokens, at file boundaries. Only supported with the text format.", value_hint = ValueHint::Other, required = false)]
+    pub chunk_tokens: Option<usize>,
+    #[arg(long, help = "When the output would exceed the specified number of estimated tokens, drop whole files, starting from the largest ones, until it fits. The dropped files are listed at the end of the output.", value_hint = ValueHint::Other, required = false)]
+    pub max_total_tokens: Option<usize>,
+    #[arg(long, help = "Only emit the content of the files smaller than the specified size, in bytes or with a unit (e.g. 10KB). The larger files get a stub with their size and number of lines.", value_hint = ValueHint::Other, required = false, value_parser = utils::parse_size)]
+    pub inline_under: Option<u64>,
+    #[arg(long, help = "The comma-separated extensions of the files to keep the longest with --max-total-tokens, most important first (e.g. 'rs,toml'). Files with other extensions are dropped first.", value_hint = ValueHint::Other, required = false, value_delimiter = ',')]
+    pub priority_ext: Option<Vec<String>>,
+    #[arg(
+        long,
+        help = "Fail instead of overwriting the output file if it already exists.",
+        required = false,
+        default_value = "false",
+        overrides_with = "force"
+    )]
+    pub no_clobber: bool,
+    #[arg(
+        long,
+        help = "Overwrite the output file if it already exists, even with --no-clobber (e.g. from a config file).",
+        required = false,
+        default_value = "false",
+        overrides_with = "no_clobber"
+    )]
+    pub force: bool,
+    #[arg(short, long, help = "Exclude files or directories matching the specified glob pattern (supports *, **, ?, {a,b}, [0-9] and \\ escapes).", value_hint = ValueHint::Other, required = false, value_parser = utils::parse_glob, num_args = 0.., action = ArgAction::Append)]
     pub exclude: Option<Vec<Glob>>,
+    #[arg(long, help = "Exclude the directories (and all their content) matching the specified glob pattern, but not the files matching it.", value_hint = ValueHint::Other, required = false, value_parser = utils::parse_glob, action = ArgAction::Append)]
+    pub exclude_dir: Option<Vec<Glob>>,
+    #[arg(long, help = "Exclude the files matching the specified glob pattern, but not the directories matching it.", value_hint = ValueHint::Other, required = false, value_parser = utils::parse_glob, action = ArgAction::Append)]
+    pub exclude_file: Option<Vec<Glob>>,
+    #[arg(long, help = "Exclude files or directories matching the patterns listed in the specified file (one per line, '#' for comments). Unlike in a gitignore, '!pattern' negations are not supported and the patterns are not anchored to the directory of the file.", value_hint = ValueHint::FilePath, required = false)]
+    pub exclude_from: Option<PathBuf>,
+    #[arg(long, help = "Exclude the usual build artifacts and dependencies of an ecosystem. Can be used multiple times.", required = false, action = ArgAction::Append)]
+    pub preset: Option<Vec<Preset>>,
+    #[arg(
+        long,
+        help = "Exclude well-known generated and vendored files (minified files, source maps, lockfiles and snapshots). Combines with --preset.",
+        required = false,
+        default_value = "false"
+    )]
+    pub skip_generated: bool,
+    #[arg(
+        long,
+        help = "Skip the text files that look minified, the lines of their first 16 KiB being longer than 2000 bytes on average. The files included with --include-name are kept.",
+        required = false,
+        default_value = "false"
+    )]
+    pub skip_minified: bool,
+    #[arg(long, help = "Only include files with one of the specified comma-separated extensions (e.g. 'rs,toml,md'). Add an empty entry (e.g. 'rs,') to include extensionless files.", value_hint = ValueHint::Other, required = false, value_delimiter = ',')]
+    pub ext: Option<Vec<String>>,
+    #[arg(long, help = "Always include the files with exactly the specified name (e.g. 'Dockerfile'), even if --ext or an exclude pattern would leave them out. Can be used multiple times.", value_hint = ValueHint::Other, required = false, action = ArgAction::Append)]
+    pub include_name: Option<Vec<String>>,
+    #[arg(long, help = "Whether --include-name or the exclude patterns win for the files matching both.", required = false, value_enum, default_value_t = Precedence::Include)]
+    pub precedence: Precedence,
     #[arg(
         long,
         help = "Exit on non-UTF-8 content.",
@@ -20,6 +116,45 @@ pub struct Args {
         default_value = "false"
     )]
     pub exit_on_non_utf8: bool,
+    #[arg(long, help = "How to handle binary (non UTF-8) files.", required = false, value_enum, default_value_t = BinaryMode::Skip)]
+    pub binary: BinaryMode,
+    #[arg(long, help = "The encoding of the text files. With an encoding other than UTF-8, a byte order mark takes precedence.", required = false, value_enum, default_value_t = TextEncoding::Utf8)]
+    pub encoding: TextEncoding,
+    #[arg(long, help = "Skip files larger than the specified size, in bytes or with a unit (e.g. 500KB, 1MB, 2GiB).", value_hint = ValueHint::Other, required = false, value_parser = utils::parse_size)]
+    pub max_file_size: Option<u64>,
+    #[arg(long, help = "Skip files smaller than the specified size, in bytes or with a unit (e.g. 1, 100B, 1KB), such as empty marker files. The files included with --include-name are kept.", value_hint = ValueHint::Other, required = false, value_parser = utils::parse_size)]
+    pub min_file_size: Option<u64>,
+    #[arg(long, help = "Fail if the files of a scanned archive extract to more than the specified size, with the same units as --max-file-size (defaults to 1GB). The excluded entries do not count.", value_hint = ValueHint::Other, required = false, value_parser = utils::parse_size)]
+    pub max_archive_size: Option<u64>,
+    #[arg(long, help = "Only include the files modified within the specified duration from now, given with a unit (e.g. 30m, 2h, 7d, 1w). The files whose modification time is not available are excluded.", value_hint = ValueHint::Other, required = false, value_parser = utils::parse_duration)]
+    pub modified_within: Option<Duration>,
+    #[arg(long, help = "Include at most the specified number of files per directory, the first ones in the walk order. The others are counted in a '... (N more files omitted)' line of the directory tree.", value_hint = ValueHint::Other, required = false)]
+    pub max_files_per_dir: Option<usize>,
+    #[arg(
+        long,
+        help = "Advanced: memory map the files of 16 MiB or more instead of reading them in memory. UTF-8 files are validated in place and their mapping kept as their content. The files must not be modified during the run.",
+        required = false,
+        default_value = "false"
+    )]
+    pub mmap: bool,
+    #[arg(
+        long,
+        help = "The maximum number of files read at once, which is also the number of worker threads. Lower it to bound the memory used on constrained machines. Defaults to the available parallelism.",
+        value_hint = ValueHint::Other,
+        required = false,
+        value_parser = RangedU64ValueParser::<usize>::new().range(1..)
+    )]
+    pub concurrency: Option<usize>,
+    #[arg(long, help = "Truncate the lines longer than the specified number of characters.", value_hint = ValueHint::Other, required = false)]
+    pub max_line_length: Option<usize>,
+    #[arg(
+        long,
+        help = "Expand the tabs of the indentation of the files to spaces, with a tab stop every specified number of columns. The tabs after the indentation are kept.",
+        value_hint = ValueHint::Other,
+        required = false,
+        value_parser = RangedU64ValueParser::<usize>::new().range(1..)
+    )]
+    pub tabs_to_spaces: Option<usize>,
     #[arg(
         long,
         help = "Do not consider the ignore files (.gitignore, .hgignore, .ignore, .git/info/exclude and core.excludesFile in .git/config).",
@@ -27,6 +162,20 @@ pub struct Args {
         default_value = "false"
     )]
     pub do_not_consider_ignore_files: bool,
+    #[arg(
+        long,
+        help = "Do not consider the ignore files of the scanned directory itself (nor .git/info/exclude and core.excludesFile), only the ones of its subdirectories.",
+        required = false,
+        default_value = "false"
+    )]
+    pub skip_root_gitignore: bool,
+    #[arg(
+        long,
+        help = "Only include the files tracked by git (as listed by 'git ls-files'), even if they are not ignored.",
+        required = false,
+        default_value = "false"
+    )]
+    pub tracked_only: bool,
     #[arg(
         long,
         help = "Include .git directory in the search.",
@@ -34,7 +183,171 @@ pub struct Args {
         default_value = "false"
     )]
     pub dangerously_allow_dot_git_traversal: bool,
-    #[arg(short, long, help = "Maximum depth to walk into the directory tree.", value_hint = ValueHint::Other, required = false)]
+    #[arg(
+        long,
+        help = "Exclude the directories of all the common version control systems (.git, .hg, .svn, .bzr, _darcs, .jj and .pijul), instead of only .git.",
+        required = false,
+        default_value = "false"
+    )]
+    pub exclude_vcs: bool,
+    #[arg(
+        long,
+        help = "Render file paths as absolute paths instead of relative to the scanned directory.",
+        required = false,
+        default_value = "false"
+    )]
+    pub absolute_paths: bool,
+    #[arg(
+        long,
+        help = "How to render the paths of the files, both in the '<file>' tags and in the leaves of the directory tree. By default, the tags have relative paths and the tree the file names.",
+        required = false,
+        value_enum,
+        conflicts_with = "absolute_paths"
+    )]
+    pub print_path_style: Option<PathStyle>,
+    #[arg(long, help = "Render file paths, and the root of the directory tree, relative to the specified ancestor of the scanned directory (e.g. the root of the repository) instead of the scanned directory itself.", value_hint = ValueHint::DirPath, required = false, conflicts_with = "absolute_paths")]
+    pub relative_to: Option<PathBuf>,
+    #[arg(long, help = "Only output the part of the directory tree under the specified path, relative to the scanned directory. The root of the tree is kept, along with the directories leading to the path.", value_hint = ValueHint::AnyPath, required = false)]
+    pub focus: Option<PathBuf>,
+    #[arg(long, help = "Resume an interrupted run: skip the entries that the walk reaches before the specified path, relative to the scanned directory, along with the path itself if it is a file. The same --stable-sort as the interrupted run must be used.", value_hint = ValueHint::AnyPath, required = false)]
+    pub after: Option<PathBuf>,
+    #[arg(
+        long,
+        help = "List the files that would be included on stdout, without reading them or writing the output file.",
+        required = false,
+        default_value = "false"
+    )]
+    pub dry_run: bool,
+    #[arg(
+        long,
+        help = "Print the number of files that would be included, their total size and the estimated number of tokens of their content, without reading them or writing the output file.",
+        required = false,
+        default_value = "false",
+        conflicts_with = "dry_run"
+    )]
+    pub count_only: bool,
+    #[arg(
+        long,
+        help = "Print the SHA-256 of the output to stderr, in the format of sha256sum, e.g. to skip the next steps of a pipeline when the output did not change.",
+        required = false,
+        default_value = "false"
+    )]
+    pub print_hash: bool,
+    #[arg(
+        long,
+        help = "Write the SHA-256 of each output file to a sidecar file next to it (e.g. output.txt.sha256), which 'sha256sum -c' can check.",
+        required = false,
+        default_value = "false",
+        conflicts_with = "output_dir"
+    )]
+    pub hash_file: bool,
+    #[arg(
+        long,
+        help = "Exit on errors encountered while walking the directory tree, instead of skipping the entries, and ignoring the ignore files, that could not be read.",
+        required = false,
+        default_value = "false"
+    )]
+    pub strict_walk: bool,
+    #[arg(
+        long,
+        help = "Remove the comments (and Python docstrings) of the files of recognized languages.",
+        required = false,
+        default_value = "false"
+    )]
+    pub strip_comments: bool,
+    #[arg(
+        long,
+        help = "Convert the line endings of the files to LF and strip their byte order mark.",
+        required = false,
+        default_value = "false"
+    )]
+    pub normalize_newlines: bool,
+    #[arg(
+        long,
+        help = "Add the modification time of each file (RFC 3339) as a 'modified' attribute.",
+        required = false,
+        default_value = "false"
+    )]
+    pub show_mtime: bool,
+    #[arg(
+        long,
+        help = "Start the text output with a comment holding the scanned directory, the generation time and the number of files.",
+        required = false,
+        default_value = "false"
+    )]
+    pub header: bool,
+    #[arg(
+        long,
+        help = "Start the text output with a comment holding the current git branch and commit (e.g. 'main@a1b2c3d'), if the scanned directory is inside a git repository.",
+        required = false,
+        default_value = "false"
+    )]
+    pub git_info: bool,
+    #[arg(
+        long,
+        help = "List the number of files per language between the directory tree and the files.",
+        required = false,
+        default_value = "false"
+    )]
+    pub toc: bool,
+    #[arg(long, help = "The order of the directories and files at each level of the tree, which the files of the output follow.", required = false, value_enum, default_value_t = TreeOrder::DirsFirst)]
+    pub tree_order: TreeOrder,
+    #[arg(
+        long,
+        help = "Leave the directory tree out of the text output, and sort the files by their relative path instead of following the tree.",
+        required = false,
+        default_value = "false"
+    )]
+    pub flat: bool,
+    #[arg(long, help = "The name of the root of the directory tree, instead of the name of the scanned directory.", value_hint = ValueHint::Other, required = false)]
+    pub root_label: Option<String>,
+    #[arg(long, help = "Sort the entries of each directory by the Unicode NFC normalization of their names (optionally lowercased with '=nfc-lowercase'), so that the output is the same on every platform.", required = false, value_enum, num_args = 0..=1, require_equals = true, default_missing_value = "nfc")]
+    pub stable_sort: Option<StableSort>,
+    #[arg(
+        long,
+        help = "Emit the content of empty files, which are only listed in the directory tree by default.",
+        required = false,
+        default_value = "false"
+    )]
+    pub include_empty_files: bool,
+    #[arg(
+        long,
+        help = "Emit the files as '<file>' tags without the 'path' attribute, which saves tokens on large outputs with deep paths. Only the directory tree then tells the files apart.",
+        required = false,
+        default_value = "false"
+    )]
+    pub no_path_attr: bool,
+    #[arg(long, help = "The string written after each '</file>' tag of the text output, e.g. '\\n---\\n' (supports the \\n, \\t and \\\\ escapes). Default: a newline.", value_hint = ValueHint::Other, required = false, value_parser = utils::parse_escapes)]
+    pub file_separator: Option<String>,
+    #[arg(long, help = "The template of each file of the text output, with the {path}, {content}, {lang}, {lines} and {attributes} placeholders, e.g. '## {path}\\n```{lang}\\n{content}\\n```' (supports the \\n, \\t and \\\\ escapes, and {{ and }} for literal braces). Default: '<file{attributes}>\\n{content}\\n</file>'.", value_hint = ValueHint::Other, required = false, value_parser = utils::parse_escapes)]
+    pub file_template: Option<String>,
+    #[arg(long, help = "The template of the directory tree of the text output, with the {tree} placeholder, e.g. '```\\n{tree}\\n```' (supports the \\n, \\t and \\\\ escapes, and {{ and }} for literal braces). Default: '<directory_tree>\\n{tree}\\n</directory_tree>'.", value_hint = ValueHint::Other, required = false, value_parser = utils::parse_escapes)]
+    pub tree_template: Option<String>,
+    #[arg(
+        long,
+        help = "Keep running and regenerate the output whenever a file that is not ignored changes.",
+        required = false,
+        default_value = "false"
+    )]
+    pub watch: bool,
+    #[cfg(feature = "signatures")]
+    #[arg(
+        long,
+        help = "Only output the declarations (functions, structs, classes, ...) of the files of recognized languages (Rust, Python).",
+        required = false,
+        default_value = "false"
+    )]
+    pub signatures_only: bool,
+    #[cfg(feature = "signatures")]
+    #[arg(
+        long,
+        help = "What to do with the files of unrecognized languages when using --signatures-only.",
+        value_enum,
+        default_value_t = SignaturesFallback::Full,
+        requires = "signatures_only"
+    )]
+    pub signatures_fallback: SignaturesFallback,
+    #[arg(short, long, help = "Maximum depth to walk into the directory tree. Like 'tree -L', 1 only lists the direct entries of the scanned directory, and 0 is rejected.", value_hint = ValueHint::Other, required = false, value_parser = utils::parse_max_depth)]
     pub max_depth: Option<usize>,
     #[arg(
         short,
@@ -44,8 +357,76 @@ pub struct Args {
         default_value = "false"
     )]
     pub follow_symbolic_links: bool,
-    #[command(flatten)]
-    pub verbosity: Verbosity<InfoLevel>,
+    #[arg(long, help = "Skip the entries reached through more than the specified number of symbolic links, when following them.", value_hint = ValueHint::Other, required = false)]
+    pub max_symlink_depth: Option<usize>,
+    #[arg(short, long, help = "Increase logging verbosity.", action = ArgAction::Count, global = true)]
+    pub verbose: u8,
+    #[arg(
+        short,
+        long,
+        help = "Only log warnings and errors, and do not print the progress bar and the timing line. Takes precedence over --verbose.",
+        required = false,
+        default_value = "false",
+        global = true
+    )]
+    pub quiet: bool,
+    #[arg(
+        long,
+        help = "Also write the logs to the specified file, without colors, including the debug logs whatever the verbosity.",
+        value_hint = ValueHint::FilePath,
+        required = false
+    )]
+    pub log_file: Option<PathBuf>,
+    #[arg(long, help = "The format of the logs: 'human' (colored) or 'json' (one JSON object per line, with the timestamp, level, message and location).", required = false, value_enum, default_value_t = LogFormat::Human)]
+    pub log_format: LogFormat,
+    #[arg(
+        long,
+        help = "Do not show the progress bar of the files being read, which is otherwise shown when stderr is a terminal, unless --quiet is given.",
+        required = false,
+        default_value = "false"
+    )]
+    pub no_progress: bool,
+    #[arg(
+        long,
+        help = "Print to stderr, after the walk, every excluded path and why it was excluded (gitignore rule, exclude pattern, too large, binary, etc.).",
+        required = false,
+        default_value = "false"
+    )]
+    pub list_excluded: bool,
+    #[arg(
+        long,
+        help = "Warn about the --exclude, --exclude-dir, --exclude-file, --exclude-from and CUNW_EXCLUDE patterns that did not exclude anything, which is usually a typo.",
+        required = false,
+        default_value = "false"
+    )]
+    pub warn_unused_patterns: bool,
+    #[arg(
+        long,
+        help = "Scan the files for probable secrets (known key formats, hardcoded credentials, high entropy strings) and list them with their line after the walk. The output is not modified.",
+        required = false,
+        default_value = "false"
+    )]
+    pub flag_secrets: bool,
+}
+
+impl Args {
+    /// Returns whether an existing output file may be overwritten, `--force` taking
+    /// precedence over `--no-clobber`.
+    pub fn clobber(&self) -> bool {
+        self.force || !self.no_clobber
+    }
+
+    /// Returns the log level filter to use, `--quiet` taking precedence over `--verbose`.
+    pub fn log_level_filter(&self) -> LevelFilter {
+        if self.quiet {
+            return LevelFilter::Warn;
+        }
+        match self.verbose {
+            0 => LevelFilter::Info,
+            1 => LevelFilter::Debug,
+            _ => LevelFilter::Trace,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -55,45 +436,399 @@ mod tests {
 
     #[test]
     fn test_default_args() {
-        let args = Args::parse_from(&["cunw", "/path/to/codebase"]);
+        let args = Args::parse_from(["cunw", "/path/to/codebase"]);
         assert_eq!(args.path.to_str().unwrap(), "/path/to/codebase");
-        assert_eq!(args.output, Some(std::path::PathBuf::from("output.txt")));
+        assert_eq!(args.config, None);
+        assert!(!args.print_config);
+        assert_eq!(args.output, None);
+        assert_eq!(args.output_dir, None);
+        assert!(!args.name_output_after_dir);
+        assert_eq!(args.format, OutputFormat::Text);
+        assert_eq!(args.chunk_tokens, None);
+        assert_eq!(args.max_total_tokens, None);
+        assert_eq!(args.inline_under, None);
+        assert_eq!(args.priority_ext, None);
+        assert!(!args.no_clobber);
+        assert!(!args.force);
+        assert!(args.clobber());
         assert_eq!(args.exclude, None);
-        assert_eq!(args.exit_on_non_utf8, false);
-        assert_eq!(args.do_not_consider_ignore_files, false);
-        assert_eq!(args.dangerously_allow_dot_git_traversal, false);
+        assert_eq!(args.exclude_dir, None);
+        assert_eq!(args.exclude_file, None);
+        assert_eq!(args.exclude_from, None);
+        assert_eq!(args.ext, None);
+        assert_eq!(args.include_name, None);
+        assert_eq!(args.precedence, Precedence::Include);
+        assert_eq!(args.preset, None);
+        assert!(!args.skip_generated);
+        assert!(!args.skip_minified);
+        assert!(!args.exit_on_non_utf8);
+        assert_eq!(args.binary, BinaryMode::Skip);
+        assert_eq!(args.encoding, TextEncoding::Utf8);
+        assert_eq!(args.max_file_size, None);
+        assert_eq!(args.min_file_size, None);
+        assert_eq!(args.max_archive_size, None);
+        assert_eq!(args.max_files_per_dir, None);
+        assert_eq!(args.modified_within, None);
+        assert!(!args.mmap);
+        assert_eq!(args.concurrency, None);
+        assert_eq!(args.max_line_length, None);
+        assert_eq!(args.tabs_to_spaces, None);
+        assert!(!args.do_not_consider_ignore_files);
+        assert!(!args.skip_root_gitignore);
+        assert!(!args.tracked_only);
+        assert!(!args.dangerously_allow_dot_git_traversal);
+        assert!(!args.exclude_vcs);
+        assert!(!args.absolute_paths);
+        assert_eq!(args.print_path_style, None);
+        assert_eq!(args.relative_to, None);
+        assert_eq!(args.focus, None);
+        assert_eq!(args.after, None);
+        assert!(!args.dry_run);
+        assert!(!args.count_only);
+        assert!(!args.print_hash);
+        assert!(!args.hash_file);
+        assert!(!args.strict_walk);
+        assert!(!args.strip_comments);
+        assert!(!args.normalize_newlines);
+        assert!(!args.show_mtime);
+        assert!(!args.header);
+        assert!(!args.git_info);
+        assert!(!args.toc);
+        assert!(!args.json_pretty);
+        assert_eq!(args.tree_order, TreeOrder::DirsFirst);
+        assert!(!args.flat);
+        assert_eq!(args.root_label, None);
+        assert_eq!(args.stable_sort, None);
+        assert!(!args.include_empty_files);
+        assert!(!args.no_path_attr);
+        assert_eq!(args.file_separator, None);
+        assert_eq!(args.file_template, None);
+        assert_eq!(args.tree_template, None);
+        assert!(!args.watch);
         assert_eq!(args.max_depth, None);
-        assert_eq!(args.follow_symbolic_links, false);
+        assert!(!args.follow_symbolic_links);
+        assert_eq!(args.max_symlink_depth, None);
+        assert!(!args.quiet);
+        assert_eq!(args.log_file, None);
+        assert_eq!(args.log_format, LogFormat::Human);
+        assert!(!args.no_progress);
+        assert!(!args.list_excluded);
+        assert!(!args.warn_unused_patterns);
+        assert!(!args.flag_secrets);
+        assert_eq!(args.log_level_filter(), log::LevelFilter::Info);
     }
 
     #[test]
     fn test_custom_args() {
-        let args = Args::parse_from(&[
+        let args = Args::parse_from([
             "cunw",
             "/path/to/codebase",
+            "--config",
+            "cunw.toml",
+            "--print-config",
             "-o",
             "custom_output.md",
+            "--name-output-after-dir",
+            "--format",
+            "json",
+            "--chunk-tokens",
+            "8000",
+            "--max-total-tokens",
+            "100000",
+            "--inline-under",
+            "10KB",
+            "--priority-ext",
+            "rs,toml",
+            "--no-clobber",
             "-e",
             "*.txt",
+            "--exclude-dir",
+            "build",
+            "--exclude-file",
+            "*.lock",
+            "--exclude-from",
+            ".cunwexclude",
+            "--ext",
+            "rs,toml,",
+            "--include-name",
+            "Dockerfile",
+            "--include-name",
+            "Makefile",
+            "--precedence",
+            "exclude",
+            "--preset",
+            "rust",
+            "--preset",
+            "node",
+            "--skip-generated",
+            "--skip-minified",
             "--exit-on-non-utf8",
+            "--binary",
+            "base64",
+            "--encoding",
+            "utf16le",
+            "--max-file-size",
+            "1KiB",
+            "--min-file-size",
+            "1",
+            "--max-archive-size",
+            "10MiB",
+            "--max-files-per-dir",
+            "20",
+            "--modified-within",
+            "7d",
+            "--mmap",
+            "--concurrency",
+            "2",
+            "--max-line-length",
+            "200",
+            "--tabs-to-spaces",
+            "4",
             "--do-not-consider-ignore-files",
+            "--skip-root-gitignore",
+            "--tracked-only",
             "--dangerously-allow-dot-git-traversal",
+            "--exclude-vcs",
+            "--absolute-paths",
+            "--dry-run",
+            "--print-hash",
+            "--hash-file",
+            "--strict-walk",
+            "--strip-comments",
+            "--normalize-newlines",
+            "--show-mtime",
+            "--header",
+            "--focus",
+            "src",
+            "--after",
+            "src/lib.rs",
+            "--git-info",
+            "--toc",
+            "--json-pretty",
+            "--tree-order",
+            "mixed",
+            "--flat",
+            "--root-label",
+            "myproject",
+            "--stable-sort=nfc-lowercase",
+            "--include-empty-files",
+            "--no-path-attr",
+            "--file-separator",
+            "\\n---\\n",
+            "--file-template",
+            "## {path}\\n{content}",
+            "--tree-template",
+            "```\\n{tree}\\n```",
+            "--watch",
             "-m",
             "3",
             "-f",
+            "--max-symlink-depth",
+            "2",
             "-v",
+            "--no-progress",
+            "--log-file",
+            "cunw.log",
+            "--log-format",
+            "json",
+            "--list-excluded",
+            "--warn-unused-patterns",
+            "--flag-secrets",
         ]);
         assert_eq!(args.path.to_str().unwrap(), "/path/to/codebase");
+        assert_eq!(args.config, Some(std::path::PathBuf::from("cunw.toml")));
+        assert!(args.print_config);
         assert_eq!(
             args.output,
             Some(std::path::PathBuf::from("custom_output.md"))
         );
-        assert_eq!(args.exclude.unwrap()[0].glob(), "*.txt");
-        assert_eq!(args.exit_on_non_utf8, true);
-        assert_eq!(args.do_not_consider_ignore_files, true);
-        assert_eq!(args.dangerously_allow_dot_git_traversal, true);
+        assert_eq!(args.format, OutputFormat::Json);
+        assert_eq!(args.chunk_tokens, Some(8000));
+        assert_eq!(args.max_total_tokens, Some(100000));
+        assert_eq!(args.inline_under, Some(10_000));
+        assert_eq!(
+            args.priority_ext,
+            Some(vec!["rs".to_string(), "toml".to_string()])
+        );
+        assert!(args.no_clobber);
+        assert!(!args.clobber());
+        assert_eq!(args.exclude.as_ref().unwrap()[0].glob(), "*.txt");
+        assert_eq!(args.exclude_dir.as_ref().unwrap()[0].glob(), "build");
+        assert_eq!(args.exclude_file.as_ref().unwrap()[0].glob(), "*.lock");
+        assert_eq!(
+            args.exclude_from,
+            Some(std::path::PathBuf::from(".cunwexclude"))
+        );
+        assert_eq!(
+            args.ext,
+            Some(vec!["rs".to_string(), "toml".to_string(), "".to_string()])
+        );
+        assert_eq!(
+            args.include_name,
+            Some(vec!["Dockerfile".to_string(), "Makefile".to_string()])
+        );
+        assert_eq!(args.precedence, Precedence::Exclude);
+        assert_eq!(args.preset, Some(vec![Preset::Rust, Preset::Node]));
+        assert!(args.skip_generated);
+        assert!(args.skip_minified);
+        assert!(args.exit_on_non_utf8);
+        assert_eq!(args.binary, BinaryMode::Base64);
+        assert_eq!(args.encoding, TextEncoding::Utf16le);
+        assert_eq!(args.max_file_size, Some(1024));
+        assert_eq!(args.min_file_size, Some(1));
+        assert_eq!(args.max_archive_size, Some(10_485_760));
+        assert_eq!(args.max_files_per_dir, Some(20));
+        assert_eq!(args.modified_within, Some(Duration::from_secs(604_800)));
+        assert!(args.mmap);
+        assert_eq!(args.concurrency, Some(2));
+        assert_eq!(args.max_line_length, Some(200));
+        assert_eq!(args.tabs_to_spaces, Some(4));
+        assert!(args.do_not_consider_ignore_files);
+        assert!(args.skip_root_gitignore);
+        assert!(args.tracked_only);
+        assert!(args.dangerously_allow_dot_git_traversal);
+        assert!(args.exclude_vcs);
+        assert!(args.absolute_paths);
+        assert!(args.dry_run);
+        assert!(args.print_hash);
+        assert!(args.hash_file);
+        assert!(args.strict_walk);
+        assert!(args.strip_comments);
+        assert!(args.normalize_newlines);
+        assert!(args.show_mtime);
+        assert!(args.header);
+        assert_eq!(args.focus, Some(PathBuf::from("src")));
+        assert_eq!(args.after, Some(PathBuf::from("src/lib.rs")));
+        assert!(args.git_info);
+        assert!(args.toc);
+        assert!(args.json_pretty);
+        assert_eq!(args.tree_order, TreeOrder::Mixed);
+        assert!(args.flat);
+        assert_eq!(args.root_label, Some("myproject".to_string()));
+        assert_eq!(args.stable_sort, Some(StableSort::NfcLowercase));
+        assert!(args.include_empty_files);
+        assert!(args.no_path_attr);
+        assert_eq!(args.file_separator, Some("\n---\n".to_string()));
+        assert_eq!(args.file_template, Some("## {path}\n{content}".to_string()));
+        assert_eq!(args.tree_template, Some("```\n{tree}\n```".to_string()));
+        assert!(args.watch);
         assert_eq!(args.max_depth, Some(3));
-        assert_eq!(args.follow_symbolic_links, true);
-        assert_eq!(args.verbosity.log_level_filter(), log::LevelFilter::Debug);
+        assert!(args.follow_symbolic_links);
+        assert_eq!(args.max_symlink_depth, Some(2));
+        assert_eq!(args.log_level_filter(), log::LevelFilter::Debug);
+        assert!(args.no_progress);
+        assert_eq!(args.log_file, Some(PathBuf::from("cunw.log")));
+        assert_eq!(args.log_format, LogFormat::Json);
+        assert!(args.list_excluded);
+        assert!(args.warn_unused_patterns);
+        assert!(args.flag_secrets);
+    }
+
+    #[test]
+    fn test_stable_sort_defaults_to_nfc() {
+        let args = Args::parse_from(["cunw", "--stable-sort", "/path/to/codebase"]);
+        assert_eq!(args.stable_sort, Some(StableSort::Nfc));
+        assert_eq!(args.path.to_str().unwrap(), "/path/to/codebase");
+    }
+
+    #[test]
+    fn test_print_path_style() {
+        let args = Args::parse_from(["cunw", "--print-path-style", "basename", "/path"]);
+        assert_eq!(args.print_path_style, Some(PathStyle::Basename));
+        assert!(Args::try_parse_from([
+            "cunw",
+            "--print-path-style",
+            "relative",
+            "--absolute-paths",
+            "/path"
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn test_relative_to_conflicts_with_absolute_paths() {
+        let args = Args::parse_from(["cunw", "--relative-to", "..", "/path/to/codebase/src"]);
+        assert_eq!(args.relative_to, Some(PathBuf::from("..")));
+        assert!(Args::try_parse_from([
+            "cunw",
+            "--relative-to",
+            "..",
+            "--absolute-paths",
+            "/path/to/codebase/src"
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn test_zero_concurrency_is_rejected() {
+        assert!(Args::try_parse_from(["cunw", "--concurrency", "0", "/path"]).is_err());
+    }
+
+    #[test]
+    fn test_zero_max_depth_is_rejected() {
+        assert!(Args::try_parse_from(["cunw", "--max-depth", "0", "/path"]).is_err());
+        let args = Args::parse_from(["cunw", "--max-depth", "1", "/path"]);
+        assert_eq!(args.max_depth, Some(1));
+    }
+
+    #[test]
+    fn test_count_only_conflicts_with_dry_run() {
+        let args = Args::parse_from(["cunw", "--count-only", "/path/to/codebase"]);
+        assert!(args.count_only);
+        assert!(Args::try_parse_from(["cunw", "--count-only", "--dry-run", "/path"]).is_err());
+    }
+
+    #[test]
+    fn test_output_dir_conflicts_with_output() {
+        let args = Args::parse_from(["cunw", "--output-dir", "out", "/path/to/codebase"]);
+        assert_eq!(args.output_dir, Some(PathBuf::from("out")));
+        assert!(
+            Args::try_parse_from(["cunw", "--output-dir", "out", "-o", "a.txt", "/path"]).is_err()
+        );
+        assert!(Args::try_parse_from([
+            "cunw",
+            "--output-dir",
+            "out",
+            "--chunk-tokens",
+            "1000",
+            "/path"
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn test_quiet_wins_over_verbose() {
+        let args = Args::parse_from(["cunw", "/path/to/codebase", "-vv", "--quiet"]);
+        assert!(args.quiet);
+        assert_eq!(args.log_level_filter(), log::LevelFilter::Warn);
+    }
+
+    #[test]
+    fn test_force_overrides_no_clobber() {
+        let args = Args::parse_from(["cunw", "/path/to/codebase", "--no-clobber", "--force"]);
+        assert!(args.force);
+        assert!(args.clobber());
+
+        let args = Args::parse_from(["cunw", "/path/to/codebase", "--force", "--no-clobber"]);
+        assert!(!args.force);
+        assert!(!args.clobber());
+    }
+
+    #[cfg(feature = "signatures")]
+    #[test]
+    fn test_signatures_args() {
+        let args = Args::parse_from(["cunw", "/path/to/codebase"]);
+        assert!(!args.signatures_only);
+        assert_eq!(args.signatures_fallback, SignaturesFallback::Full);
+
+        let args = Args::parse_from([
+            "cunw",
+            "/path/to/codebase",
+            "--signatures-only",
+            "--signatures-fallback",
+            "skip",
+        ]);
+        assert!(args.signatures_only);
+        assert_eq!(args.signatures_fallback, SignaturesFallback::Skip);
     }
 }
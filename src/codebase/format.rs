@@ -0,0 +1,108 @@
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+/// The format of the generated output.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    /// The directory tree and the content of each file, wrapped in XML-like tags.
+    #[default]
+    Text,
+    /// A JSON document containing the directory tree and the content of each file.
+    Json,
+    /// A single XML document, with a `<codebase>` root element containing the
+    /// directory tree and the content of each file.
+    Xml,
+    /// JSON Lines: a leading line with the directory tree, then one JSON object per
+    /// file, so that the output can be processed incrementally.
+    #[serde(rename = "json-lines")]
+    #[value(alias = "jsonl")]
+    JsonLines,
+    /// A self-contained HTML page, with a collapsible directory tree and each file in
+    /// a `<pre><code>` block.
+    Html,
+}
+
+/// How the paths of the files are rendered, in the `<file>` tags and the leaves of
+/// the directory tree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PathStyle {
+    /// The absolute path of the file.
+    Absolute,
+    /// The path relative to the scanned directory (or to `--relative-to`).
+    Relative,
+    /// The name of the file only, the directory tree telling where it is.
+    Basename,
+}
+
+/// A serializable representation of a [`Codebase`](super::Codebase).
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SerializableCodebase {
+    /// The directory tree, starting at the root.
+    pub tree: SerializableDirectory,
+    /// The files, in the order they appear in the text output.
+    pub files: Vec<SerializableFile>,
+    /// Whether the build was interrupted, meaning that the codebase is incomplete.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub interrupted: bool,
+    /// The files dropped to fit within the maximum number of tokens.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub omitted: Vec<String>,
+}
+
+/// The leading line of the [`OutputFormat::JsonLines`] output, holding everything
+/// of a [`SerializableCodebase`] but the files.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SerializableMetadata<'a> {
+    /// The directory tree, starting at the root.
+    pub tree: &'a SerializableDirectory,
+    /// Whether the build was interrupted, meaning that the codebase is incomplete.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub interrupted: bool,
+    /// The files dropped to fit within the maximum number of tokens.
+    #[serde(skip_serializing_if = "<[String]>::is_empty")]
+    pub omitted: &'a [String],
+}
+
+/// A serializable representation of a directory of the tree.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SerializableDirectory {
+    /// The name of the directory, or its path relative to the directory it is listed
+    /// in when the tree is too deep to be nested further.
+    pub name: String,
+    /// The child directories, along with all their descendants once the tree is too
+    /// deep to be nested further.
+    pub directories: Vec<SerializableDirectory>,
+    /// The names of the child files.
+    pub files: Vec<String>,
+    /// The number of child files left out, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub omitted_files: Option<usize>,
+}
+
+/// A serializable representation of a file and its content.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SerializableFile {
+    /// The path of the file, as displayed in the text output.
+    pub path: String,
+    /// The content of the file, left out for the files too large to be inlined
+    /// (see `--inline-under`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content: Option<String>,
+    /// Whether the content was left out, the file being too large to be inlined.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub omitted: bool,
+    /// The size of the file in bytes, only set when its content is left out.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+    /// The number of lines of the file, only set when its text content is left out.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lines: Option<usize>,
+    /// The encoding of the content, only set for binary files (`"base64"`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<String>,
+    /// The modification time of the file (RFC 3339), only set with `--show-mtime`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub modified: Option<String>,
+}
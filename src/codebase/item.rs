@@ -1,17 +1,176 @@
 use std::{
     fmt::Display,
-    path::PathBuf,
+    ops::Deref,
+    path::{Path, PathBuf},
     sync::{Arc, OnceLock},
+    time::SystemTime,
 };
 
-use tokio::{fs, task::JoinHandle};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::{DateTime, SecondsFormat, Utc};
+use clap::ValueEnum;
+use futures::{stream, Stream};
+use memmap2::Mmap;
+use serde::{Deserialize, Serialize};
+use tokio::{
+    fs,
+    io::{AsyncBufReadExt, BufReader},
+    sync::Semaphore,
+    task::JoinHandle,
+};
+
+use crate::{
+    error::{CunwError, Result},
+    logger::Logger,
+};
+
+/// The default size from which files are memory mapped, when enabled.
+pub const MMAP_THRESHOLD: u64 = 16 * 1024 * 1024;
+
+/// How to handle binary (non UTF-8) files.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BinaryMode {
+    /// Skip binary files.
+    #[default]
+    Skip,
+    /// Emit binary files encoded in base64.
+    Base64,
+}
+
+/// The encoding used to decode the text files.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TextEncoding {
+    /// UTF-8.
+    #[default]
+    Utf8,
+    /// UTF-16, little endian.
+    Utf16le,
+    /// UTF-16, big endian.
+    Utf16be,
+    /// Latin-1, decoded as its Windows-1252 superset.
+    Latin1,
+}
+
+impl TextEncoding {
+    fn encoding(&self) -> &'static encoding_rs::Encoding {
+        match self {
+            TextEncoding::Utf8 => encoding_rs::UTF_8,
+            TextEncoding::Utf16le => encoding_rs::UTF_16LE,
+            TextEncoding::Utf16be => encoding_rs::UTF_16BE,
+            TextEncoding::Latin1 => encoding_rs::WINDOWS_1252,
+        }
+    }
+}
 
-use crate::error::{CunwError, Result};
+/// The encoding of a loaded file content.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentEncoding {
+    /// The content is the UTF-8 text of the file.
+    Text,
+    /// The content is the base64 encoding of a binary file.
+    Base64,
+}
+
+/// The bytes of a file, either read in memory or memory mapped.
+enum FileBytes {
+    Read(Vec<u8>),
+    Mapped(Mmap),
+}
+
+impl FileBytes {
+    /// Reads the file at `path`, memory mapping it if it is at least `mmap_threshold`
+    /// bytes. Falls back to a regular read if it cannot be mapped.
+    async fn load(path: &Path, mmap_threshold: Option<u64>) -> std::io::Result<Self> {
+        if let Some(mmap_threshold) = mmap_threshold {
+            match Self::map(path, mmap_threshold) {
+                Ok(Some(map)) => return Ok(FileBytes::Mapped(map)),
+                Ok(None) => {}
+                Err(err) => Logger::debug(
+                    format!(
+                        "Failed to memory map {}, reading it instead: {}",
+                        path.display(),
+                        err
+                    )
+                    .as_str(),
+                ),
+            }
+        }
+        fs::read(path).await.map(FileBytes::Read)
+    }
+    /// Memory maps the file at `path`, or returns [`None`] if it is smaller than
+    /// `mmap_threshold`, too small to be worth it.
+    fn map(path: &Path, mmap_threshold: u64) -> std::io::Result<Option<Mmap>> {
+        let file = std::fs::File::open(path)?;
+        if file.metadata()?.len() < mmap_threshold {
+            return Ok(None);
+        }
+        // SAFETY: Nothing is written through the mapping, which is either dropped once
+        // decoded or kept as the content of a UTF-8 file. This is only sound as long as
+        // the file is not modified meanwhile, which the `mmap` option documents: the
+        // content could change under us (and stop being valid UTF-8), and a file
+        // truncated while mapped makes the reads past its new end raise SIGBUS, which
+        // aborts the process
+        unsafe { Mmap::map(&file) }.map(Some)
+    }
+}
+
+impl Deref for FileBytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            FileBytes::Read(bytes) => bytes,
+            FileBytes::Mapped(map) => map,
+        }
+    }
+}
+
+/// The loaded content of a file, which dereferences to its text.
+#[derive(Debug)]
+pub enum FileContent {
+    /// The text of the file, read in memory.
+    Text(String),
+    /// A memory mapping of a file validated as UTF-8, whose text starts at `start`
+    /// (after the byte order mark, if any), so that the file is not held twice.
+    Mapped { map: Mmap, start: usize },
+}
+
+impl FileContent {
+    /// Returns the text of the file.
+    pub fn as_str(&self) -> &str {
+        match self {
+            FileContent::Text(text) => text,
+            // SAFETY: The mapping was validated as UTF-8 when loaded, and the files must
+            // not be modified while mapped, see `FileBytes::map`
+            FileContent::Mapped { map, start } => unsafe {
+                std::str::from_utf8_unchecked(&map[*start..])
+            },
+        }
+    }
+}
+
+impl Deref for FileContent {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct CodebaseItem {
     pub path: PathBuf,
-    pub content: Arc<OnceLock<String>>,
+    pub content: Arc<OnceLock<FileContent>>,
+    pub encoding: Arc<OnceLock<ContentEncoding>>,
+    /// The modification time of the file, only recorded when requested.
+    pub modified: Option<SystemTime>,
+    /// The size of the file in bytes, as reported by the filesystem when it was walked.
+    pub size: Option<u64>,
+    /// The target of the symbolic link, as stored in the link (so possibly relative),
+    /// for the links that are not followed. Their content is not loaded.
+    pub symlink_target: Option<PathBuf>,
 }
 
 impl CodebaseItem {
@@ -19,22 +178,142 @@ impl CodebaseItem {
         Self {
             path,
             content: Arc::new(OnceLock::new()),
+            encoding: Arc::new(OnceLock::new()),
+            modified: None,
+            size: None,
+            symlink_target: None,
+        }
+    }
+    /// Creates an item for a symbolic link that is not followed, pointing to `target`.
+    pub fn symlink(path: PathBuf, target: PathBuf) -> Self {
+        Self {
+            symlink_target: Some(target),
+            ..Self::new(path)
         }
     }
-    pub fn eventually_load_content(&self) -> JoinHandle<Result<()>> {
+    /// Reads and decodes the content of the file in the background, unless it was
+    /// already loaded.
+    ///
+    /// With the UTF-8 `text_encoding`, files that are not valid UTF-8 are handled
+    /// according to `binary_mode`. With the other encodings, a byte order mark takes
+    /// precedence, and invalid sequences are replaced with a warning.
+    ///
+    /// With `mmap_threshold`, the files of at least that many bytes are memory mapped
+    /// instead of being read in memory. A valid UTF-8 file is validated in place and
+    /// its mapping kept as its content, while the other encodings are decoded from it.
+    ///
+    /// The file is only read once a permit of `permits` is acquired, which bounds the
+    /// number of files held in memory at once.
+    pub fn eventually_load_content(
+        &self,
+        binary_mode: BinaryMode,
+        text_encoding: TextEncoding,
+        mmap_threshold: Option<u64>,
+        permits: Arc<Semaphore>,
+    ) -> JoinHandle<Result<()>> {
         let _content = self.content.clone();
+        let _encoding = self.encoding.clone();
         let _path = self.path.clone();
         tokio::spawn(async move {
+            let _permit = permits
+                .acquire_owned()
+                .await
+                .expect("The semaphore is never closed");
             let path = _path;
-            if let None = _content.get() {
-                let file_content = fs::read_to_string(&path)
-                    .await
-                    .map_err(|e| CunwError::new(e.into()).with_file(path.clone()))?;
+            if _content.get().is_none() {
+                let (file_content, encoding) = if text_encoding == TextEncoding::Utf8 {
+                    let bytes = FileBytes::load(&path, mmap_threshold)
+                        .await
+                        .map_err(|e| CunwError::new(e.into()).with_file(path.clone()))?;
+                    // Validated in place, so that a mapping is not copied
+                    match std::str::from_utf8(&bytes) {
+                        Ok(text) => {
+                            // A leading byte order mark would show up as a stray
+                            // character at the start of the file
+                            let start = if text.starts_with('\u{feff}') {
+                                '\u{feff}'.len_utf8()
+                            } else {
+                                0
+                            };
+                            let text = match bytes {
+                                FileBytes::Read(mut bytes) => {
+                                    bytes.drain(..start);
+                                    // SAFETY: Validated as UTF-8 above, and only whole
+                                    // characters were removed
+                                    FileContent::Text(unsafe { String::from_utf8_unchecked(bytes) })
+                                }
+                                FileBytes::Mapped(map) => FileContent::Mapped { map, start },
+                            };
+                            (text, ContentEncoding::Text)
+                        }
+                        Err(_) if binary_mode == BinaryMode::Base64 => (
+                            FileContent::Text(STANDARD.encode(&*bytes)),
+                            ContentEncoding::Base64,
+                        ),
+                        Err(err) => {
+                            let err = std::io::Error::new(std::io::ErrorKind::InvalidData, err);
+                            return Err(CunwError::new(err.into()).with_file(path.clone()));
+                        }
+                    }
+                } else {
+                    let bytes = FileBytes::load(&path, mmap_threshold)
+                        .await
+                        .map_err(|e| CunwError::new(e.into()).with_file(path.clone()))?;
+                    // Follows and strips the byte order mark, if any
+                    let (text, text_encoding, had_errors) = text_encoding.encoding().decode(&bytes);
+                    if had_errors {
+                        Logger::warn(
+                            format!(
+                                "{} is not valid {}, the invalid sequences were replaced",
+                                path.display(),
+                                text_encoding.name()
+                            )
+                            .as_str(),
+                        );
+                    }
+                    (FileContent::Text(text.into_owned()), ContentEncoding::Text)
+                };
                 _content.get_or_init(|| file_content);
+                _encoding.get_or_init(|| encoding);
             }
             Ok(())
         })
     }
+    /// Streams the lines of the file, without their terminators, instead of loading
+    /// the whole content in memory.
+    ///
+    /// The file is opened on the first poll. The stream ends after the first error,
+    /// which includes files that are not valid UTF-8.
+    pub fn content_stream(&self) -> impl Stream<Item = Result<String>> + Send + 'static {
+        let path = self.path.clone();
+        stream::unfold(Some((None, path)), |state| async move {
+            let (lines, path) = state?;
+            let mut lines = match lines {
+                Some(lines) => lines,
+                None => match fs::File::open(&path).await {
+                    Ok(file) => BufReader::new(file).lines(),
+                    Err(err) => {
+                        return Some((Err(CunwError::new(err.into()).with_file(path)), None))
+                    }
+                },
+            };
+            match lines.next_line().await {
+                Ok(Some(line)) => Some((Ok(line), Some((Some(lines), path)))),
+                Ok(None) => None,
+                Err(err) => Some((Err(CunwError::new(err.into()).with_file(path)), None)),
+            }
+        })
+    }
+    /// Returns the modification time of the file formatted as RFC 3339, if it was recorded.
+    pub fn modified_rfc3339(&self) -> Option<String> {
+        self.modified.map(|modified| {
+            DateTime::<Utc>::from(modified).to_rfc3339_opts(SecondsFormat::Secs, true)
+        })
+    }
+    /// Returns whether the loaded content is the base64 encoding of a binary file.
+    pub fn is_base64(&self) -> bool {
+        self.encoding.get() == Some(&ContentEncoding::Base64)
+    }
 }
 
 impl PartialEq for CodebaseItem {
@@ -46,6 +325,10 @@ impl PartialEq for CodebaseItem {
 impl Display for CodebaseItem {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         // Only print the file name (or directory name) instead of the full path.
-        write!(f, "{}", self.path.file_name().unwrap().to_str().unwrap())
+        write!(f, "{}", self.path.file_name().unwrap().to_str().unwrap())?;
+        if let Some(target) = &self.symlink_target {
+            write!(f, " -> {}", target.display())?;
+        }
+        Ok(())
     }
 }
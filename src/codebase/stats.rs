@@ -0,0 +1,430 @@
+use std::{
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
+use globset::Glob;
+
+use crate::{logger::Logger, secrets::ProbableSecret, utils};
+
+/// The maximum number of files listed in the summary of the files skipped for their size.
+const MAX_LISTED_SKIPPED_FOR_SIZE: usize = 10;
+/// The maximum number of files listed in the summary of the files that look minified.
+const MAX_LISTED_SKIPPED_MINIFIED: usize = 10;
+
+/// Why an entry was left out of a [`Codebase`](super::Codebase).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExclusionReason {
+    /// The entry is hidden (on Windows).
+    Hidden,
+    /// The entry is ignored by the given gitignore rule.
+    Gitignore(String),
+    /// The entry matches an exclude pattern (`--exclude`, a preset, ...).
+    Pattern,
+    /// The directory matches a directory exclude pattern.
+    DirectoryPattern,
+    /// The file matches a file exclude pattern.
+    FilePattern,
+    /// The entry is not tracked by git.
+    Untracked,
+    /// The entry is reached through too many symbolic links.
+    SymlinkDepth,
+    /// The extension of the file is not in the allowlist.
+    Extension,
+    /// The file is larger than the maximum file size (its size in bytes).
+    TooLarge(u64),
+    /// The file is smaller than the minimum file size (its size in bytes).
+    TooSmall(u64),
+    /// The file is not valid text in the expected encoding.
+    Binary,
+    /// The file was not modified within the requested duration.
+    NotModifiedRecently,
+    /// The modification time of the file is not available, although it was needed.
+    NoModificationTime,
+    /// The directory of the file already holds the maximum number of files.
+    DirectoryFull,
+    /// The file looks minified, its lines being very long.
+    Minified,
+}
+
+impl Display for ExclusionReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExclusionReason::Hidden => write!(f, "hidden"),
+            ExclusionReason::Gitignore(rule) => write!(f, "gitignore rule {}", rule),
+            ExclusionReason::Pattern => write!(f, "exclude pattern"),
+            ExclusionReason::DirectoryPattern => write!(f, "directory exclude pattern"),
+            ExclusionReason::FilePattern => write!(f, "file exclude pattern"),
+            ExclusionReason::Untracked => write!(f, "not tracked by git"),
+            ExclusionReason::SymlinkDepth => write!(f, "too many symbolic links"),
+            ExclusionReason::Extension => write!(f, "extension not allowed"),
+            ExclusionReason::TooLarge(size) => {
+                write!(f, "too large ({})", utils::format_size(*size))
+            }
+            ExclusionReason::TooSmall(size) => {
+                write!(f, "too small ({})", utils::format_size(*size))
+            }
+            ExclusionReason::Binary => write!(f, "binary"),
+            ExclusionReason::NotModifiedRecently => write!(f, "not modified recently"),
+            ExclusionReason::NoModificationTime => write!(f, "no modification time"),
+            ExclusionReason::DirectoryFull => write!(f, "too many files in the directory"),
+            ExclusionReason::Minified => write!(f, "looks minified"),
+        }
+    }
+}
+
+/// Statistics collected while building a [`Codebase`](super::Codebase).
+#[derive(Debug, Default, Clone)]
+pub struct CodebaseStats {
+    /// Entries that could not be walked (along with the error), meaning that
+    /// they and all their descendants are missing from the codebase.
+    pub skipped_due_to_error: Vec<(PathBuf, String)>,
+    /// Files that were larger than `max_file_size` (along with their size in bytes),
+    /// relative to the root.
+    pub skipped_due_to_size: Vec<(PathBuf, u64)>,
+    /// The maximum file size the files were checked against, if any.
+    pub max_file_size: Option<u64>,
+    /// Files that looked minified, relative to the root.
+    pub skipped_minified: Vec<PathBuf>,
+    /// The entries left out of the codebase (along with the reason), relative to the
+    /// root. The content of excluded directories is not listed.
+    pub excluded: Vec<(PathBuf, ExclusionReason)>,
+    /// The probable secrets found in the files (along with their path relative to the
+    /// root), if they were scanned.
+    pub probable_secrets: Vec<(PathBuf, ProbableSecret)>,
+    /// The number of directories walked, not counting the root.
+    pub directories: usize,
+    /// The number of files added to the codebase by the walk, not counting the
+    /// symbolic links.
+    pub files: usize,
+    /// The total size of these files in bytes, as reported by the filesystem.
+    pub bytes: u64,
+    /// The depth of the deepest entry of the codebase, the direct entries of the root
+    /// being at depth 1.
+    pub max_depth_reached: usize,
+    /// The deepest entry of the codebase (the first found at `max_depth_reached`),
+    /// relative to the root.
+    pub deepest_path: Option<PathBuf>,
+}
+
+impl CodebaseStats {
+    /// Returns the number of entries that could not be walked.
+    pub fn skipped_due_to_error_count(&self) -> usize {
+        self.skipped_due_to_error.len()
+    }
+
+    /// Records an entry of the codebase found at `depth`.
+    pub(crate) fn record_entry(&mut self, relative_path: &Path, depth: usize, is_dir: bool) {
+        if is_dir {
+            self.directories += 1;
+        }
+        if depth > self.max_depth_reached {
+            self.max_depth_reached = depth;
+            self.deepest_path = Some(relative_path.to_path_buf());
+        }
+    }
+
+    /// Records a file of `bytes` bytes added to the codebase.
+    pub(crate) fn record_file(&mut self, bytes: u64) {
+        self.files += 1;
+        self.bytes += bytes;
+    }
+
+    /// Logs a summary of the statistics.
+    pub fn log_summary(&self) {
+        Logger::info(self.walk_summary().as_str());
+        if !self.skipped_due_to_error.is_empty() {
+            Logger::warn(
+                format!(
+                    "{} entries could not be read and were skipped, the output is incomplete:",
+                    self.skipped_due_to_error_count()
+                )
+                .as_str(),
+            );
+            for (path, err) in &self.skipped_due_to_error {
+                Logger::warn(format!("  - {} ({})", path.display(), err).as_str());
+            }
+            Logger::warn("If you want to exit on such errors, use the --strict-walk flag.");
+        }
+        if let (Some(max_file_size), false) =
+            (self.max_file_size, self.skipped_due_to_size.is_empty())
+        {
+            Logger::info(self.skipped_due_to_size_summary(max_file_size).as_str());
+        }
+        if !self.skipped_minified.is_empty() {
+            Logger::info(self.skipped_minified_summary().as_str());
+            Logger::info("To keep some of them, add their names with --include-name.");
+        }
+        if !self.probable_secrets.is_empty() {
+            Logger::warn(self.probable_secrets_summary().as_str());
+            for (path, secret) in &self.probable_secrets {
+                Logger::warn(
+                    format!("  - {}:{} ({})", path.display(), secret.line, secret.kind).as_str(),
+                );
+            }
+            Logger::warn("The output was not modified, review them before sharing it.");
+        }
+    }
+
+    /// Returns whether `glob` matches one of the entries excluded by a pattern, the
+    /// `kind` of the pattern telling whether it only applies to the directories
+    /// ([`ExclusionReason::DirectoryPattern`]) or the files
+    /// ([`ExclusionReason::FilePattern`]).
+    ///
+    /// Like the walk, the glob is matched against the paths relative to `root` as
+    /// well as the absolute paths.
+    pub fn is_pattern_used(&self, root: &Path, glob: &Glob, kind: &ExclusionReason) -> bool {
+        let matcher = glob.compile_matcher();
+        self.excluded.iter().any(|(path, reason)| {
+            let path = path.to_string_lossy();
+            let (path, is_dir) = match path.strip_suffix('/') {
+                Some(path) => (path, true),
+                None => (path.as_ref(), false),
+            };
+            let applies = match kind {
+                ExclusionReason::DirectoryPattern => is_dir,
+                ExclusionReason::FilePattern => !is_dir,
+                _ => true,
+            };
+            let excluded_by_pattern = matches!(
+                reason,
+                ExclusionReason::Pattern
+                    | ExclusionReason::DirectoryPattern
+                    | ExclusionReason::FilePattern
+            );
+            applies
+                && excluded_by_pattern
+                && (matcher.is_match(path) || matcher.is_match(root.join(path)))
+        })
+    }
+
+    /// Returns the excluded entries with their reason, one per line (e.g.
+    /// `target/: exclude pattern`), directories ending with a `/`.
+    pub fn excluded_listing(&self) -> String {
+        self.excluded
+            .iter()
+            .map(|(path, reason)| format!("{}: {}\n", path.display(), reason))
+            .collect()
+    }
+
+    /// Returns a summary such as
+    /// `Walked 12 directories and 30 files (1.2MB), up to depth 4 (src/a/b/c.rs)`.
+    fn walk_summary(&self) -> String {
+        let mut summary = format!(
+            "Walked {} director{} and {} file{} ({}), up to depth {}",
+            self.directories,
+            if self.directories == 1 { "y" } else { "ies" },
+            self.files,
+            if self.files == 1 { "" } else { "s" },
+            utils::format_size(self.bytes),
+            self.max_depth_reached
+        );
+        if let Some(deepest_path) = &self.deepest_path {
+            summary.push_str(format!(" ({})", deepest_path.display()).as_str());
+        }
+        summary
+    }
+
+    /// Returns a summary such as `Found 3 probable secrets in 2 files:`.
+    fn probable_secrets_summary(&self) -> String {
+        let files = self
+            .probable_secrets
+            .iter()
+            .map(|(path, _)| path)
+            .collect::<std::collections::HashSet<_>>()
+            .len();
+        format!(
+            "Found {} probable secret{} in {} file{}:",
+            self.probable_secrets.len(),
+            if self.probable_secrets.len() == 1 {
+                ""
+            } else {
+                "s"
+            },
+            files,
+            if files == 1 { "" } else { "s" }
+        )
+    }
+
+    /// Returns a summary such as `Skipped 2 files over 1MB: bundle.js (4.2MB), ...`.
+    fn skipped_due_to_size_summary(&self, max_file_size: u64) -> String {
+        let mut listed = self
+            .skipped_due_to_size
+            .iter()
+            .take(MAX_LISTED_SKIPPED_FOR_SIZE)
+            .map(|(path, size)| format!("{} ({})", path.display(), utils::format_size(*size)))
+            .collect::<Vec<_>>();
+        let remaining = self.skipped_due_to_size.len().saturating_sub(listed.len());
+        if remaining > 0 {
+            listed.push(format!("and {} more", remaining));
+        }
+        format!(
+            "Skipped {} file{} over {}: {}",
+            self.skipped_due_to_size.len(),
+            if self.skipped_due_to_size.len() == 1 {
+                ""
+            } else {
+                "s"
+            },
+            utils::format_size(max_file_size),
+            listed.join(", ")
+        )
+    }
+
+    /// Returns a summary such as `Skipped 2 files that look minified: app.min.js, ...`.
+    fn skipped_minified_summary(&self) -> String {
+        let mut listed = self
+            .skipped_minified
+            .iter()
+            .take(MAX_LISTED_SKIPPED_MINIFIED)
+            .map(|path| path.display().to_string())
+            .collect::<Vec<_>>();
+        let remaining = self.skipped_minified.len().saturating_sub(listed.len());
+        if remaining > 0 {
+            listed.push(format!("and {} more", remaining));
+        }
+        format!(
+            "Skipped {} file{} that look{} minified: {}",
+            self.skipped_minified.len(),
+            if self.skipped_minified.len() == 1 {
+                ""
+            } else {
+                "s"
+            },
+            if self.skipped_minified.len() == 1 {
+                "s"
+            } else {
+                ""
+            },
+            listed.join(", ")
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::secrets::SecretKind;
+
+    #[test]
+    fn test_excluded_listing() {
+        let stats = CodebaseStats {
+            excluded: vec![
+                (PathBuf::from("target/"), ExclusionReason::Pattern),
+                (
+                    PathBuf::from("debug.log"),
+                    ExclusionReason::Gitignore("*.log (.gitignore)".to_string()),
+                ),
+                (
+                    PathBuf::from("bundle.js"),
+                    ExclusionReason::TooLarge(4_200_000),
+                ),
+            ],
+            ..Default::default()
+        };
+        assert_eq!(
+            stats.excluded_listing(),
+            "target/: exclude pattern\ndebug.log: gitignore rule *.log (.gitignore)\nbundle.js: too large (4.2MB)\n"
+        );
+    }
+
+    #[test]
+    fn test_is_pattern_used() {
+        let stats = CodebaseStats {
+            excluded: vec![
+                (PathBuf::from("target/"), ExclusionReason::Pattern),
+                (PathBuf::from("src/build"), ExclusionReason::FilePattern),
+                (
+                    PathBuf::from("debug.log"),
+                    ExclusionReason::Gitignore("*.log".to_string()),
+                ),
+            ],
+            ..Default::default()
+        };
+        let root = Path::new("/project");
+        let is_used = |pattern: &str, kind: ExclusionReason| {
+            stats.is_pattern_used(root, &Glob::new(pattern).unwrap(), &kind)
+        };
+        assert!(is_used("target", ExclusionReason::Pattern));
+        assert!(is_used("/project/target", ExclusionReason::Pattern));
+        assert!(is_used("target", ExclusionReason::DirectoryPattern));
+        assert!(is_used("**/build", ExclusionReason::FilePattern));
+        // A typo, a pattern of the wrong kind, and a path excluded by a gitignore
+        assert!(!is_used("targte", ExclusionReason::Pattern));
+        assert!(!is_used("**/build", ExclusionReason::DirectoryPattern));
+        assert!(!is_used("*.log", ExclusionReason::Pattern));
+    }
+
+    #[test]
+    fn test_walk_summary() {
+        let mut stats = CodebaseStats::default();
+        assert_eq!(
+            stats.walk_summary(),
+            "Walked 0 directories and 0 files (0B), up to depth 0"
+        );
+        stats.record_entry(Path::new("src"), 1, true);
+        stats.record_entry(Path::new("src/a"), 2, true);
+        stats.record_entry(Path::new("src/a/b.rs"), 3, false);
+        stats.record_entry(Path::new("src/a/c.rs"), 3, false);
+        stats.record_entry(Path::new("README.md"), 1, false);
+        stats.record_file(1500);
+        assert_eq!(
+            stats.walk_summary(),
+            "Walked 2 directories and 1 file (1.5KB), up to depth 3 (src/a/b.rs)"
+        );
+    }
+
+    #[test]
+    fn test_probable_secrets_summary() {
+        let secret = |line| ProbableSecret {
+            line,
+            kind: SecretKind::HighEntropy,
+        };
+        let stats = CodebaseStats {
+            probable_secrets: vec![
+                (PathBuf::from(".env"), secret(1)),
+                (PathBuf::from(".env"), secret(2)),
+                (PathBuf::from("src/config.rs"), secret(12)),
+            ],
+            ..Default::default()
+        };
+        assert_eq!(
+            stats.probable_secrets_summary(),
+            "Found 3 probable secrets in 2 files:"
+        );
+    }
+
+    #[test]
+    fn test_skipped_due_to_size_summary() {
+        let stats = CodebaseStats {
+            skipped_due_to_size: vec![
+                (PathBuf::from("bundle.js"), 4_200_000),
+                (PathBuf::from("data.json"), 1_500_000),
+            ],
+            max_file_size: Some(1_000_000),
+            ..Default::default()
+        };
+        assert_eq!(
+            stats.skipped_due_to_size_summary(1_000_000),
+            "Skipped 2 files over 1MB: bundle.js (4.2MB), data.json (1.5MB)"
+        );
+    }
+
+    #[test]
+    fn test_skipped_minified_summary() {
+        let mut stats = CodebaseStats {
+            skipped_minified: vec![PathBuf::from("app.min.js")],
+            ..Default::default()
+        };
+        assert_eq!(
+            stats.skipped_minified_summary(),
+            "Skipped 1 file that looks minified: app.min.js"
+        );
+        stats.skipped_minified = (0..12)
+            .map(|i| PathBuf::from(format!("{}.js", i)))
+            .collect();
+        assert!(stats
+            .skipped_minified_summary()
+            .ends_with("8.js, 9.js, and 2 more"));
+    }
+}
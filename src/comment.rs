@@ -0,0 +1,431 @@
+use crate::language::Language;
+
+/// Describes how comments and string literals are written in a language.
+struct CommentSyntax {
+    /// Markers starting a comment that runs until the end of the line.
+    line: &'static [&'static str],
+    /// Markers opening and closing a block comment.
+    block: Option<(&'static str, &'static str)>,
+    /// Whether block comments can be nested (e.g. Rust).
+    nested_blocks: bool,
+    /// Characters delimiting string (and character) literals.
+    quotes: &'static [u8],
+    /// Whether string literals can span multiple lines. Backtick strings always can.
+    multiline_strings: bool,
+    /// Whether `"""` and `'''` delimit (multiline) string literals.
+    triple_quotes: bool,
+    /// Whether triple-quoted strings used as statements are docstrings, which are stripped.
+    docstrings: bool,
+    /// Whether line comment markers must be preceded by whitespace (e.g. `$#` in shell).
+    line_after_whitespace: bool,
+}
+
+impl CommentSyntax {
+    /// Returns the syntax of a language, or [`None`] if its comments are not stripped.
+    fn of(language: Language) -> Option<Self> {
+        const C_LIKE: CommentSyntax = CommentSyntax {
+            line: &["//"],
+            block: Some(("/*", "*/")),
+            nested_blocks: false,
+            quotes: b"\"'",
+            multiline_strings: false,
+            triple_quotes: false,
+            docstrings: false,
+            line_after_whitespace: false,
+        };
+        const HASH: CommentSyntax = CommentSyntax {
+            line: &["#"],
+            block: None,
+            nested_blocks: false,
+            quotes: b"\"'",
+            multiline_strings: false,
+            triple_quotes: false,
+            docstrings: false,
+            line_after_whitespace: false,
+        };
+        let syntax = match language {
+            Language::Rust => CommentSyntax {
+                nested_blocks: true,
+                quotes: b"\"",
+                multiline_strings: true,
+                ..C_LIKE
+            },
+            Language::JavaScript | Language::TypeScript | Language::Go => CommentSyntax {
+                quotes: b"\"'`",
+                ..C_LIKE
+            },
+            Language::C | Language::Cpp | Language::Java => C_LIKE,
+            Language::Python => CommentSyntax {
+                triple_quotes: true,
+                docstrings: true,
+                ..HASH
+            },
+            Language::Shell => CommentSyntax {
+                multiline_strings: true,
+                line_after_whitespace: true,
+                ..HASH
+            },
+            Language::Toml => CommentSyntax {
+                triple_quotes: true,
+                ..HASH
+            },
+            Language::Yaml => CommentSyntax {
+                line_after_whitespace: true,
+                ..HASH
+            },
+            // Markdown is mostly prose, and its HTML comments often carry directives
+            Language::Markdown => return None,
+        };
+        Some(syntax)
+    }
+}
+
+/// Removes the line and block comments (and Python docstrings) of a source file.
+///
+/// Comments inside string literals are kept, and lines that only held a comment
+/// are removed altogether. The source of languages whose comments are not stripped
+/// (e.g. Markdown) is returned as is.
+///
+/// # Arguments
+///
+/// * `language` - The [`Language`] of the source file.
+/// * `source` - The content of the source file.
+///
+/// # Returns
+///
+/// The source without its comments.
+pub fn strip_comments(language: Language, source: &str) -> String {
+    let Some(syntax) = CommentSyntax::of(language) else {
+        return source.to_string();
+    };
+    let mut stripper = Stripper {
+        language,
+        syntax,
+        source: source.as_bytes(),
+        output: Vec::with_capacity(source.len()),
+        position: 0,
+        line_start: 0,
+        stripped_line: false,
+    };
+    stripper.run();
+    // Only whole UTF-8 sequences are copied, since all markers are ASCII
+    String::from_utf8_lossy(&stripper.output).into_owned()
+}
+
+struct Stripper<'a> {
+    language: Language,
+    syntax: CommentSyntax,
+    source: &'a [u8],
+    output: Vec<u8>,
+    /// Position in `source`.
+    position: usize,
+    /// Position in `output` of the start of the current line.
+    line_start: usize,
+    /// Whether a comment was removed from the current line.
+    stripped_line: bool,
+}
+
+impl Stripper<'_> {
+    fn run(&mut self) {
+        // Keep shebangs
+        if matches!(self.language, Language::Shell | Language::Python) && self.starts_with("#!") {
+            while self.position < self.source.len() && self.source[self.position] != b'\n' {
+                self.copy(1);
+            }
+        }
+
+        while self.position < self.source.len() {
+            let byte = self.source[self.position];
+            if byte == b'\n' {
+                self.end_line();
+                self.position += 1;
+            } else if self.syntax.triple_quotes
+                && (self.starts_with("\"\"\"") || self.starts_with("'''"))
+            {
+                let delimiter = &self.source[self.position..self.position + 3];
+                if self.syntax.docstrings && self.at_statement_start() {
+                    self.skip_delimited(delimiter);
+                } else {
+                    self.copy_string(delimiter, true);
+                }
+            } else if let Some((open, close)) =
+                self.syntax.block.filter(|(open, _)| self.starts_with(open))
+            {
+                self.skip_block(open, close);
+            } else if self.at_line_comment() {
+                while self.position < self.source.len() && self.source[self.position] != b'\n' {
+                    self.position += 1;
+                }
+                self.stripped_line = true;
+            } else if self.language == Language::Rust && self.at_raw_string() {
+                self.copy_raw_string();
+            } else if self.language == Language::Rust && byte == b'\'' {
+                self.copy_char_or_lifetime();
+            } else if self.syntax.quotes.contains(&byte) {
+                let multiline = self.syntax.multiline_strings || byte == b'`';
+                self.copy_string(&[byte], multiline);
+            } else {
+                self.copy(1);
+            }
+        }
+        self.finish_line();
+    }
+
+    fn starts_with(&self, pattern: &str) -> bool {
+        self.source[self.position..].starts_with(pattern.as_bytes())
+    }
+
+    fn copy(&mut self, length: usize) {
+        let end = (self.position + length).min(self.source.len());
+        for index in self.position..end {
+            if self.source[index] == b'\n' {
+                self.output.push(b'\n');
+                self.line_start = self.output.len();
+                self.stripped_line = false;
+            } else {
+                self.output.push(self.source[index]);
+            }
+        }
+        self.position = end;
+    }
+
+    /// Drops the current line if it only held comments, and trims the
+    /// whitespace left behind by a trailing comment otherwise.
+    fn finish_line(&mut self) -> bool {
+        if !self.stripped_line {
+            return true;
+        }
+        self.stripped_line = false;
+        while self.output.len() > self.line_start
+            && self.output.last().is_some_and(u8::is_ascii_whitespace)
+        {
+            self.output.pop();
+        }
+        self.output.len() > self.line_start
+    }
+
+    fn end_line(&mut self) {
+        if self.finish_line() {
+            self.output.push(b'\n');
+        }
+        self.line_start = self.output.len();
+    }
+
+    fn at_line_comment(&self) -> bool {
+        self.syntax
+            .line
+            .iter()
+            .any(|marker| self.starts_with(marker))
+            && (!self.syntax.line_after_whitespace
+                || self.position == 0
+                || self.source[self.position - 1].is_ascii_whitespace())
+    }
+
+    /// Whether a string starting here is on its own, as opposed to being part of an expression.
+    fn at_statement_start(&self) -> bool {
+        let line = &self.output[self.line_start..];
+        if !line.iter().all(u8::is_ascii_whitespace) {
+            return false;
+        }
+        let previous = self.output[..self.line_start]
+            .iter()
+            .rev()
+            .find(|byte| !byte.is_ascii_whitespace());
+        !matches!(
+            previous,
+            Some(b'=' | b'(' | b'[' | b'{' | b',' | b'+' | b'\\')
+        )
+    }
+
+    fn skip_delimited(&mut self, delimiter: &[u8]) {
+        let delimiter = delimiter.to_vec();
+        self.position += delimiter.len();
+        while self.position < self.source.len()
+            && !self.source[self.position..].starts_with(&delimiter)
+        {
+            self.position += if self.source[self.position] == b'\\' {
+                2
+            } else {
+                1
+            };
+        }
+        self.position = (self.position + delimiter.len()).min(self.source.len());
+        self.stripped_line = true;
+    }
+
+    fn skip_block(&mut self, open: &str, close: &str) {
+        let mut depth = 0;
+        while self.position < self.source.len() {
+            if self.starts_with(open) {
+                depth += 1;
+                self.position += open.len();
+            } else if self.starts_with(close) {
+                depth -= 1;
+                self.position += close.len();
+                if depth == 0 || !self.syntax.nested_blocks {
+                    break;
+                }
+            } else {
+                self.position += 1;
+            }
+        }
+        self.position = self.position.min(self.source.len());
+        self.stripped_line = true;
+    }
+
+    fn copy_string(&mut self, delimiter: &[u8], multiline: bool) {
+        let delimiter = delimiter.to_vec();
+        self.copy(delimiter.len());
+        while self.position < self.source.len() {
+            if self.source[self.position..].starts_with(&delimiter) {
+                self.copy(delimiter.len());
+                return;
+            }
+            match self.source[self.position] {
+                b'\\' => self.copy(2),
+                b'\n' if !multiline => return,
+                _ => self.copy(1),
+            }
+        }
+    }
+
+    fn is_identifier_byte(byte: u8) -> bool {
+        byte.is_ascii_alphanumeric() || byte == b'_'
+    }
+
+    /// Whether a Rust raw string (`r"..."`, `r#"..."#`, `br"..."`) starts here.
+    fn at_raw_string(&self) -> bool {
+        if self.source[self.position] != b'r' {
+            return false;
+        }
+        let prefix_start = match self.position.checked_sub(1).map(|index| self.source[index]) {
+            Some(b'b') => self.position - 1,
+            _ => self.position,
+        };
+        if prefix_start > 0 && Self::is_identifier_byte(self.source[prefix_start - 1]) {
+            return false;
+        }
+        let rest = &self.source[self.position + 1..];
+        let hashes = rest.iter().take_while(|byte| **byte == b'#').count();
+        rest.get(hashes) == Some(&b'"')
+    }
+
+    fn copy_raw_string(&mut self) {
+        let hashes = self.source[self.position + 1..]
+            .iter()
+            .take_while(|byte| **byte == b'#')
+            .count();
+        let mut closing = vec![b'"'];
+        closing.extend(std::iter::repeat_n(b'#', hashes));
+        self.copy(hashes + 2);
+        while self.position < self.source.len()
+            && !self.source[self.position..].starts_with(&closing)
+        {
+            self.copy(1);
+        }
+        self.copy(closing.len());
+    }
+
+    /// Copies a Rust character literal, or the quote of a lifetime or label.
+    fn copy_char_or_lifetime(&mut self) {
+        let rest = &self.source[self.position + 1..];
+        if rest.first() == Some(&b'\\') {
+            let length = rest
+                .iter()
+                .skip(2)
+                .position(|byte| *byte == b'\'')
+                .map_or(rest.len(), |index| index + 3);
+            self.copy(length + 1);
+            return;
+        }
+        let character_length = std::str::from_utf8(&rest[..rest.len().min(4)])
+            .or_else(|err| std::str::from_utf8(&rest[..err.valid_up_to()]))
+            .ok()
+            .and_then(|text| text.chars().next())
+            .map_or(1, char::len_utf8);
+        if rest.get(character_length) == Some(&b'\'') {
+            self.copy(character_length + 2);
+        } else {
+            self.copy(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_rust_comments() {
+        let source = r##"//! Crate docs
+/// Adds two numbers.
+fn add<'a>(a: &'a i32, b: i32) -> i32 {
+    // Comment
+    let url = "http://example.com"; // trailing
+    let quote = '"';
+    let raw = r#"/* not a comment */"#;
+    /* outer /* nested */ still outer */
+    *a + b
+}
+"##;
+        assert_eq!(
+            strip_comments(Language::Rust, source),
+            r##"fn add<'a>(a: &'a i32, b: i32) -> i32 {
+    let url = "http://example.com";
+    let quote = '"';
+    let raw = r#"/* not a comment */"#;
+    *a + b
+}
+"##
+        );
+    }
+
+    #[test]
+    fn test_strip_python_comments() {
+        let source = r##"#!/usr/bin/env python3
+"""Module docstring."""
+
+def greet(name):
+    """
+    Docstring.
+    """
+    # Comment
+    text = """# not a comment"""
+    return f"Hello {name} # not a comment"  # trailing
+"##;
+        assert_eq!(
+            strip_comments(Language::Python, source),
+            r##"#!/usr/bin/env python3
+
+def greet(name):
+    text = """# not a comment"""
+    return f"Hello {name} # not a comment"
+"##
+        );
+    }
+
+    #[test]
+    fn test_strip_javascript_comments() {
+        let source =
+            "const a = `// kept\n${b}`; /* block\ncomment */\nconst c = 'it\\'s'; // trailing\n";
+        assert_eq!(
+            strip_comments(Language::JavaScript, source),
+            "const a = `// kept\n${b}`;\nconst c = 'it\\'s';\n"
+        );
+    }
+
+    #[test]
+    fn test_markdown_is_kept() {
+        let source = "# Title\n<!-- markdownlint-disable -->\n";
+        assert_eq!(strip_comments(Language::Markdown, source), source);
+    }
+
+    #[test]
+    fn test_strip_shell_comments() {
+        let source = "#!/bin/sh\n# Comment\necho \"$#\" '# kept' # trailing\n";
+        assert_eq!(
+            strip_comments(Language::Shell, source),
+            "#!/bin/sh\necho \"$#\" '# kept'\n"
+        );
+    }
+}
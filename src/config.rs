@@ -0,0 +1,769 @@
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use clap::{parser::ValueSource, ArgMatches};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{
+    args::Args,
+    codebase::{
+        format::{OutputFormat, PathStyle},
+        item::{BinaryMode, TextEncoding},
+        Precedence,
+    },
+    error::{CunwError, CunwErrorKind, Result},
+    logger::Logger,
+    presets::Preset,
+    tree::{StableSort, TreeOrder},
+    utils,
+};
+
+/// Name of the config file discovered in the scanned directory.
+pub const CONFIG_FILE_NAME: &str = ".cunw.toml";
+
+/// Default values for the command line arguments, read from a `.cunw.toml` file.
+///
+/// Every field mirrors the [`Args`] field of the same name, and is only used if the
+/// argument was not given on the command line.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Config {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_dir: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name_output_after_dir: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<OutputFormat>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chunk_tokens: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_total_tokens: Option<usize>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "deserialize_size"
+    )]
+    pub inline_under: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub priority_ext: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub no_clobber: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exclude: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exclude_dir: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exclude_file: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exclude_from: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preset: Option<Vec<Preset>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skip_generated: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skip_minified: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ext: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub include_name: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub precedence: Option<Precedence>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exit_on_non_utf8: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub binary: Option<BinaryMode>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<TextEncoding>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "deserialize_size"
+    )]
+    pub max_file_size: Option<u64>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "deserialize_size"
+    )]
+    pub min_file_size: Option<u64>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "deserialize_size"
+    )]
+    pub max_archive_size: Option<u64>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "serialize_duration",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub modified_within: Option<Duration>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_files_per_dir: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mmap: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub concurrency: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_line_length: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tabs_to_spaces: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub do_not_consider_ignore_files: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exclude_vcs: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skip_root_gitignore: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tracked_only: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub absolute_paths: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub print_path_style: Option<PathStyle>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub strict_walk: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub strip_comments: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub normalize_newlines: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub show_mtime: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub header: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub git_info: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub toc: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub json_pretty: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tree_order: Option<TreeOrder>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub flat: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub root_label: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub relative_to: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub focus: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stable_sort: Option<StableSort>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub include_empty_files: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub no_path_attr: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file_separator: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file_template: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tree_template: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_depth: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub follow_symbolic_links: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_symlink_depth: Option<usize>,
+}
+
+/// Deserializes a size given either as a number of bytes or as a human readable
+/// string (e.g. `"1MB"`).
+fn deserialize_size<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Option<u64>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Size {
+        Bytes(u64),
+        Human(String),
+    }
+    match Size::deserialize(deserializer)? {
+        Size::Bytes(bytes) => Ok(Some(bytes)),
+        Size::Human(size) => utils::parse_size(&size)
+            .map(Some)
+            .map_err(|err| serde::de::Error::custom(err.source)),
+    }
+}
+
+/// Deserializes a duration given as a string with a unit (e.g. `"7d"`).
+fn deserialize_duration<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Option<Duration>, D::Error> {
+    let duration = String::deserialize(deserializer)?;
+    utils::parse_duration(&duration)
+        .map(Some)
+        .map_err(|err| serde::de::Error::custom(err.source))
+}
+
+/// Serializes a duration as a number of seconds, e.g. `"3600s"`, which
+/// [`deserialize_duration`] reads back.
+fn serialize_duration<S: Serializer>(
+    duration: &Option<Duration>,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    match duration {
+        Some(duration) => serializer.serialize_str(&format!("{}s", duration.as_secs())),
+        None => serializer.serialize_none(),
+    }
+}
+
+impl Config {
+    /// Reads the config file given with `--config`, or the `.cunw.toml` of the scanned
+    /// directory if there is one.
+    ///
+    /// Relative paths of the config file are resolved against its directory.
+    ///
+    /// **Returns**
+    ///
+    /// A [`Result`] containing an [`Option<Config>`]. Returns [`None`] if no config file
+    /// was given and the scanned directory has none.
+    pub fn discover(args: &Args) -> Result<Option<Self>> {
+        let path = match &args.config {
+            Some(path) => path.clone(),
+            None => {
+                let path = args.path.join(CONFIG_FILE_NAME);
+                if !path.is_file() {
+                    return Ok(None);
+                }
+                path
+            }
+        };
+        Logger::debug(format!("Using config file: {}", path.display()).as_str());
+
+        let content = std::fs::read_to_string(&path)
+            .map_err(|err| CunwError::new(err.into()).with_file(path.clone()))?;
+        let mut config = Self::parse(&content).map_err(|err| {
+            CunwError::new(CunwErrorKind::Config(path.clone(), err)).with_file(path.clone())
+        })?;
+        config.resolve_paths(path.parent().unwrap_or(Path::new("")));
+        Ok(Some(config))
+    }
+
+    /// Parses the content of a config file.
+    pub fn parse(content: &str) -> std::result::Result<Self, String> {
+        toml::from_str(content).map_err(|err| err.message().to_string())
+    }
+
+    fn resolve_paths(&mut self, directory: &Path) {
+        for path in [
+            &mut self.output,
+            &mut self.output_dir,
+            &mut self.exclude_from,
+            &mut self.relative_to,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            // '-' means stdout, not a file
+            if path.is_relative() && path != Path::new("-") {
+                *path = directory.join(&*path);
+            }
+        }
+    }
+
+    /// Returns the configuration equivalent to the given arguments.
+    pub fn from_args(args: &Args) -> Self {
+        Self {
+            output: args.output.clone(),
+            output_dir: args.output_dir.clone(),
+            name_output_after_dir: Some(args.name_output_after_dir),
+            format: Some(args.format),
+            chunk_tokens: args.chunk_tokens,
+            max_total_tokens: args.max_total_tokens,
+            inline_under: args.inline_under,
+            priority_ext: args.priority_ext.clone(),
+            no_clobber: Some(!args.clobber()),
+            exclude: args
+                .exclude
+                .as_ref()
+                .map(|exclude| exclude.iter().map(|glob| glob.glob().to_string()).collect()),
+            exclude_dir: args
+                .exclude_dir
+                .as_ref()
+                .map(|exclude| exclude.iter().map(|glob| glob.glob().to_string()).collect()),
+            exclude_file: args
+                .exclude_file
+                .as_ref()
+                .map(|exclude| exclude.iter().map(|glob| glob.glob().to_string()).collect()),
+            exclude_from: args.exclude_from.clone(),
+            preset: args.preset.clone(),
+            skip_generated: Some(args.skip_generated),
+            skip_minified: Some(args.skip_minified),
+            ext: args.ext.clone(),
+            include_name: args.include_name.clone(),
+            precedence: Some(args.precedence),
+            exit_on_non_utf8: Some(args.exit_on_non_utf8),
+            binary: Some(args.binary),
+            encoding: Some(args.encoding),
+            max_file_size: args.max_file_size,
+            min_file_size: args.min_file_size,
+            max_archive_size: args.max_archive_size,
+            modified_within: args.modified_within,
+            max_files_per_dir: args.max_files_per_dir,
+            mmap: Some(args.mmap),
+            concurrency: args.concurrency,
+            max_line_length: args.max_line_length,
+            tabs_to_spaces: args.tabs_to_spaces,
+            do_not_consider_ignore_files: Some(args.do_not_consider_ignore_files),
+            exclude_vcs: Some(args.exclude_vcs),
+            skip_root_gitignore: Some(args.skip_root_gitignore),
+            tracked_only: Some(args.tracked_only),
+            absolute_paths: Some(args.absolute_paths),
+            print_path_style: args.print_path_style,
+            strict_walk: Some(args.strict_walk),
+            strip_comments: Some(args.strip_comments),
+            normalize_newlines: Some(args.normalize_newlines),
+            show_mtime: Some(args.show_mtime),
+            header: Some(args.header),
+            git_info: Some(args.git_info),
+            toc: Some(args.toc),
+            json_pretty: Some(args.json_pretty),
+            tree_order: Some(args.tree_order),
+            flat: Some(args.flat),
+            root_label: args.root_label.clone(),
+            relative_to: args.relative_to.clone(),
+            focus: args.focus.clone(),
+            stable_sort: args.stable_sort,
+            include_empty_files: Some(args.include_empty_files),
+            no_path_attr: Some(args.no_path_attr),
+            file_separator: args.file_separator.clone(),
+            file_template: args.file_template.clone(),
+            tree_template: args.tree_template.clone(),
+            max_depth: args.max_depth,
+            follow_symbolic_links: Some(args.follow_symbolic_links),
+            max_symlink_depth: args.max_symlink_depth,
+        }
+    }
+
+    /// Serializes the configuration to TOML.
+    pub fn to_toml(&self) -> String {
+        toml::to_string(self).expect("Config is always serializable to TOML")
+    }
+
+    /// Applies the configuration to the arguments that were not given on the command line.
+    ///
+    /// **Arguments**
+    ///
+    /// * `args` - The parsed arguments to update.
+    /// * `matches` - The [`ArgMatches`] `args` were parsed from, used to tell the arguments
+    ///   given on the command line from the default values.
+    pub fn apply(self, args: &mut Args, matches: &ArgMatches) -> Result<()> {
+        let from_cli = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+        fn merge<T>(arg: &mut T, value: Option<T>, from_cli: bool) {
+            if let (false, Some(value)) = (from_cli, value) {
+                *arg = value;
+            }
+        }
+
+        let parse_patterns = |patterns: Option<Vec<String>>| {
+            patterns
+                .map(|patterns| {
+                    patterns
+                        .iter()
+                        .map(|pattern| {
+                            utils::parse_glob(pattern).map_err(|err| {
+                                CunwError::new(CunwErrorKind::InvalidArgument(format!(
+                                    "Invalid exclude pattern in config file: {}",
+                                    err
+                                )))
+                            })
+                        })
+                        .collect::<Result<Vec<_>>>()
+                })
+                .transpose()
+        };
+        let exclude = parse_patterns(self.exclude)?;
+        let exclude_dir = parse_patterns(self.exclude_dir)?;
+        let exclude_file = parse_patterns(self.exclude_file)?;
+
+        merge(&mut args.output, self.output.map(Some), from_cli("output"));
+        merge(
+            &mut args.output_dir,
+            self.output_dir.map(Some),
+            from_cli("output_dir"),
+        );
+        merge(
+            &mut args.name_output_after_dir,
+            self.name_output_after_dir,
+            from_cli("name_output_after_dir"),
+        );
+        merge(&mut args.format, self.format, from_cli("format"));
+        merge(
+            &mut args.chunk_tokens,
+            self.chunk_tokens.map(Some),
+            from_cli("chunk_tokens"),
+        );
+        merge(
+            &mut args.max_total_tokens,
+            self.max_total_tokens.map(Some),
+            from_cli("max_total_tokens"),
+        );
+        merge(
+            &mut args.inline_under,
+            self.inline_under.map(Some),
+            from_cli("inline_under"),
+        );
+        merge(
+            &mut args.priority_ext,
+            self.priority_ext.map(Some),
+            from_cli("priority_ext"),
+        );
+        // --force on the command line also overrides the no-clobber of the config
+        merge(
+            &mut args.no_clobber,
+            self.no_clobber,
+            from_cli("no_clobber") || from_cli("force"),
+        );
+        merge(&mut args.exclude, exclude.map(Some), from_cli("exclude"));
+        merge(
+            &mut args.exclude_dir,
+            exclude_dir.map(Some),
+            from_cli("exclude_dir"),
+        );
+        merge(
+            &mut args.exclude_file,
+            exclude_file.map(Some),
+            from_cli("exclude_file"),
+        );
+        merge(
+            &mut args.exclude_from,
+            self.exclude_from.map(Some),
+            from_cli("exclude_from"),
+        );
+        merge(&mut args.preset, self.preset.map(Some), from_cli("preset"));
+        merge(
+            &mut args.skip_generated,
+            self.skip_generated,
+            from_cli("skip_generated"),
+        );
+        merge(
+            &mut args.skip_minified,
+            self.skip_minified,
+            from_cli("skip_minified"),
+        );
+        merge(&mut args.ext, self.ext.map(Some), from_cli("ext"));
+        merge(
+            &mut args.include_name,
+            self.include_name.map(Some),
+            from_cli("include_name"),
+        );
+        merge(
+            &mut args.precedence,
+            self.precedence,
+            from_cli("precedence"),
+        );
+        merge(
+            &mut args.exit_on_non_utf8,
+            self.exit_on_non_utf8,
+            from_cli("exit_on_non_utf8"),
+        );
+        merge(&mut args.binary, self.binary, from_cli("binary"));
+        merge(&mut args.encoding, self.encoding, from_cli("encoding"));
+        merge(
+            &mut args.max_file_size,
+            self.max_file_size.map(Some),
+            from_cli("max_file_size"),
+        );
+        merge(
+            &mut args.min_file_size,
+            self.min_file_size.map(Some),
+            from_cli("min_file_size"),
+        );
+        merge(
+            &mut args.max_archive_size,
+            self.max_archive_size.map(Some),
+            from_cli("max_archive_size"),
+        );
+        merge(
+            &mut args.modified_within,
+            self.modified_within.map(Some),
+            from_cli("modified_within"),
+        );
+        merge(
+            &mut args.max_files_per_dir,
+            self.max_files_per_dir.map(Some),
+            from_cli("max_files_per_dir"),
+        );
+        merge(&mut args.mmap, self.mmap, from_cli("mmap"));
+        if self.concurrency == Some(0) {
+            return Err(CunwError::new(CunwErrorKind::InvalidArgument(
+                "Invalid concurrency in config file: it must be at least 1".to_string(),
+            )));
+        }
+        merge(
+            &mut args.concurrency,
+            self.concurrency.map(Some),
+            from_cli("concurrency"),
+        );
+        merge(
+            &mut args.max_line_length,
+            self.max_line_length.map(Some),
+            from_cli("max_line_length"),
+        );
+        if self.tabs_to_spaces == Some(0) {
+            return Err(CunwError::new(CunwErrorKind::InvalidArgument(
+                "Invalid tabs-to-spaces in config file: it must be at least 1".to_string(),
+            )));
+        }
+        merge(
+            &mut args.tabs_to_spaces,
+            self.tabs_to_spaces.map(Some),
+            from_cli("tabs_to_spaces"),
+        );
+        merge(
+            &mut args.do_not_consider_ignore_files,
+            self.do_not_consider_ignore_files,
+            from_cli("do_not_consider_ignore_files"),
+        );
+        merge(
+            &mut args.exclude_vcs,
+            self.exclude_vcs,
+            from_cli("exclude_vcs"),
+        );
+        merge(
+            &mut args.skip_root_gitignore,
+            self.skip_root_gitignore,
+            from_cli("skip_root_gitignore"),
+        );
+        merge(
+            &mut args.tracked_only,
+            self.tracked_only,
+            from_cli("tracked_only"),
+        );
+        merge(
+            &mut args.absolute_paths,
+            self.absolute_paths,
+            from_cli("absolute_paths"),
+        );
+        merge(
+            &mut args.print_path_style,
+            self.print_path_style.map(Some),
+            from_cli("print_path_style"),
+        );
+        merge(
+            &mut args.strict_walk,
+            self.strict_walk,
+            from_cli("strict_walk"),
+        );
+        merge(
+            &mut args.strip_comments,
+            self.strip_comments,
+            from_cli("strip_comments"),
+        );
+        merge(
+            &mut args.normalize_newlines,
+            self.normalize_newlines,
+            from_cli("normalize_newlines"),
+        );
+        merge(
+            &mut args.show_mtime,
+            self.show_mtime,
+            from_cli("show_mtime"),
+        );
+        merge(&mut args.header, self.header, from_cli("header"));
+        merge(&mut args.git_info, self.git_info, from_cli("git_info"));
+        merge(&mut args.toc, self.toc, from_cli("toc"));
+        merge(
+            &mut args.json_pretty,
+            self.json_pretty,
+            from_cli("json_pretty"),
+        );
+        merge(
+            &mut args.tree_order,
+            self.tree_order,
+            from_cli("tree_order"),
+        );
+        merge(&mut args.flat, self.flat, from_cli("flat"));
+        merge(
+            &mut args.root_label,
+            self.root_label.map(Some),
+            from_cli("root_label"),
+        );
+        merge(
+            &mut args.relative_to,
+            self.relative_to.map(Some),
+            from_cli("relative_to"),
+        );
+        merge(&mut args.focus, self.focus.map(Some), from_cli("focus"));
+        merge(
+            &mut args.stable_sort,
+            self.stable_sort.map(Some),
+            from_cli("stable_sort"),
+        );
+        merge(
+            &mut args.include_empty_files,
+            self.include_empty_files,
+            from_cli("include_empty_files"),
+        );
+        merge(
+            &mut args.no_path_attr,
+            self.no_path_attr,
+            from_cli("no_path_attr"),
+        );
+        merge(
+            &mut args.file_separator,
+            self.file_separator.map(Some),
+            from_cli("file_separator"),
+        );
+        merge(
+            &mut args.file_template,
+            self.file_template.map(Some),
+            from_cli("file_template"),
+        );
+        merge(
+            &mut args.tree_template,
+            self.tree_template.map(Some),
+            from_cli("tree_template"),
+        );
+        let max_depth = self.max_depth.map(utils::check_max_depth).transpose()?;
+        merge(
+            &mut args.max_depth,
+            max_depth.map(Some),
+            from_cli("max_depth"),
+        );
+        merge(
+            &mut args.follow_symbolic_links,
+            self.follow_symbolic_links,
+            from_cli("follow_symbolic_links"),
+        );
+        merge(
+            &mut args.max_symlink_depth,
+            self.max_symlink_depth.map(Some),
+            from_cli("max_symlink_depth"),
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::{CommandFactory, FromArgMatches};
+
+    fn parse_args(args: &[&str]) -> (Args, ArgMatches) {
+        let matches = Args::command().get_matches_from(args);
+        let args = Args::from_arg_matches(&matches).unwrap();
+        (args, matches)
+    }
+
+    #[test]
+    fn test_config_parse() {
+        let config = Config::parse(
+            r#"
+max-file-size = "1MB"
+exclude = ["*.lock", "docs/**"]
+format = "json"
+max-depth = 3
+preset = ["rust"]
+strip-comments = true
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.exclude,
+            Some(vec!["*.lock".to_string(), "docs/**".to_string()])
+        );
+        assert_eq!(config.format, Some(OutputFormat::Json));
+        assert_eq!(
+            Config::parse("format = \"json-lines\"").unwrap().format,
+            Some(OutputFormat::JsonLines)
+        );
+        assert_eq!(config.max_depth, Some(3));
+        assert_eq!(config.preset, Some(vec![Preset::Rust]));
+        assert_eq!(config.strip_comments, Some(true));
+        assert_eq!(config.output, None);
+        assert_eq!(config.max_file_size, Some(1_000_000));
+        assert_eq!(
+            Config::parse("max-file-size = 1024").unwrap().max_file_size,
+            Some(1024)
+        );
+        assert!(Config::parse("max-file-size = \"big\"").is_err());
+        assert_eq!(
+            Config::parse("modified-within = \"2h\"")
+                .unwrap()
+                .modified_within,
+            Some(Duration::from_secs(7200))
+        );
+        assert!(Config::parse("modified-within = \"soon\"").is_err());
+
+        assert!(Config::parse("unknown-key = 1").is_err());
+    }
+
+    #[test]
+    fn test_config_cli_overrides_config() {
+        let config = Config::parse(
+            r#"
+exclude = ["*.lock"]
+format = "json"
+max-depth = 3
+binary = "base64"
+tree-order = "files-first"
+"#,
+        )
+        .unwrap();
+        let (mut args, matches) = parse_args(&["cunw", ".", "--max-depth", "1", "-e", "*.md"]);
+        config.apply(&mut args, &matches).unwrap();
+
+        // From the config file
+        assert_eq!(args.format, OutputFormat::Json);
+        assert_eq!(args.binary, BinaryMode::Base64);
+        assert_eq!(args.tree_order, TreeOrder::FilesFirst);
+        // From the command line
+        assert_eq!(args.max_depth, Some(1));
+        assert_eq!(args.exclude.unwrap()[0].glob(), "*.md");
+        // Defaults, the output path being resolved later
+        assert_eq!(args.output, None);
+    }
+
+    #[test]
+    fn test_config_rejects_zero_max_depth_and_concurrency() {
+        for content in ["max-depth = 0", "concurrency = 0", "tabs-to-spaces = 0"] {
+            let config = Config::parse(content).unwrap();
+            let (mut args, matches) = parse_args(&["cunw", "."]);
+            assert!(config.apply(&mut args, &matches).is_err(), "{}", content);
+        }
+    }
+
+    #[test]
+    fn test_config_no_clobber() {
+        let config = Config::parse("no-clobber = true").unwrap();
+        let (mut args, matches) = parse_args(&["cunw", "."]);
+        config.clone().apply(&mut args, &matches).unwrap();
+        assert!(!args.clobber());
+
+        let (mut args, matches) = parse_args(&["cunw", ".", "--force"]);
+        config.apply(&mut args, &matches).unwrap();
+        assert!(args.clobber());
+    }
+
+    #[test]
+    fn test_config_from_args_round_trip() {
+        let (args, _) = parse_args(&[
+            "cunw",
+            ".",
+            "--format",
+            "json",
+            "-e",
+            "*.lock",
+            "--modified-within",
+            "2h",
+        ]);
+        let toml = Config::from_args(&args).to_toml();
+        assert!(toml.contains("format = \"json\""));
+        assert!(toml.contains("modified-within = \"7200s\""));
+        assert!(toml.contains("exclude = [\"*.lock\"]"));
+        assert!(!toml.contains("max-depth"));
+        assert_eq!(Config::parse(&toml).unwrap(), Config::from_args(&args));
+    }
+
+    #[test]
+    fn test_config_relative_paths() {
+        let mut config = Config::parse("output = \"out.txt\"").unwrap();
+        config.resolve_paths(Path::new("project"));
+        assert_eq!(config.output, Some(Path::new("project").join("out.txt")));
+
+        let mut config = Config::parse("output = \"-\"").unwrap();
+        config.resolve_paths(Path::new("project"));
+        assert_eq!(config.output, Some(PathBuf::from("-")));
+    }
+}
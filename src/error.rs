@@ -40,9 +40,49 @@ pub enum CunwErrorKind {
     #[diagnostic(code(cunw::codebase_build_error))]
     CodebaseBuild(String),
 
+    #[error("The path to scan does not exist or is not accessible: {} ({1})", .0.display())]
+    #[diagnostic(code(cunw::invalid_root))]
+    InvalidRoot(PathBuf, #[source] std::io::Error),
+
+    #[error("The path to scan must be a directory, but {} is a file", .0.display())]
+    #[diagnostic(code(cunw::root_is_file))]
+    RootIsFile(PathBuf),
+
+    #[error("{} is not an ancestor of the scanned directory {}", .0.display(), .1.display())]
+    #[diagnostic(code(cunw::not_an_ancestor))]
+    NotAnAncestor(PathBuf, PathBuf),
+
+    #[error("Refusing to write {} outside of the output directory", .0.display())]
+    #[diagnostic(code(cunw::path_outside_output_dir))]
+    PathOutsideOutputDir(PathBuf),
+
+    #[error("Failed to extract the archive {}: {1}", .0.display())]
+    #[diagnostic(code(cunw::archive_error))]
+    Archive(PathBuf, String),
+
+    #[error("Invalid argument: {0}")]
+    #[diagnostic(code(cunw::invalid_argument))]
+    InvalidArgument(String),
+
+    #[error("Failed to serialize to JSON: {0}")]
+    #[diagnostic(code(cunw::json_error))]
+    Json(#[from] serde_json::Error),
+
     #[error("Failed to build gitignore: {0}")]
     #[diagnostic(code(cunw::gitignore_build_error))]
     GitignoreBuild(#[from] ignore::Error),
+
+    #[error("Invalid config file {}: {1}", .0.display())]
+    #[diagnostic(code(cunw::config_error))]
+    Config(PathBuf, String),
+
+    #[error("The output file {} already exists, use --force to overwrite it", .0.display())]
+    #[diagnostic(code(cunw::output_exists))]
+    OutputExists(PathBuf),
+
+    #[error("Failed to watch for changes: {0}")]
+    #[diagnostic(code(cunw::watch_error))]
+    Watch(#[from] notify::Error),
 }
 
 pub type Result<T> = std::result::Result<T, CunwError>;
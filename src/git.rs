@@ -0,0 +1,190 @@
+use std::{
+    collections::HashSet,
+    fmt::Display,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use crate::logger::Logger;
+
+/// The files tracked by git under a directory, along with their parent directories.
+#[derive(Debug, Clone, Default)]
+pub struct TrackedFiles {
+    files: HashSet<PathBuf>,
+    directories: HashSet<PathBuf>,
+}
+
+impl TrackedFiles {
+    /// Lists the files tracked by git under `root` with `git ls-files`.
+    ///
+    /// **Arguments**
+    ///
+    /// * `root` - A reference to the (canonicalized) [`Path`] of the directory.
+    ///
+    /// **Returns**
+    ///
+    /// An [`Option<TrackedFiles>`] holding absolute paths. Returns [`None`] if `root`
+    /// is not inside a git repository, or if git could not be run.
+    pub fn list(root: &Path) -> Option<Self> {
+        let stdout = run_git(root, &["ls-files", "-z"])?;
+
+        // Paths are relative to the directory git was run in
+        let mut tracked = Self::default();
+        for relative_path in stdout
+            .split(|byte| *byte == 0)
+            .filter(|path| !path.is_empty())
+        {
+            let path = root.join(String::from_utf8_lossy(relative_path).as_ref());
+            for directory in path.ancestors().skip(1) {
+                if !tracked.directories.insert(directory.to_path_buf()) || directory == root {
+                    break;
+                }
+            }
+            tracked.files.insert(path);
+        }
+        Some(tracked)
+    }
+
+    /// Returns whether `path` is a tracked file.
+    pub fn contains_file(&self, path: &Path) -> bool {
+        self.files.contains(path)
+    }
+
+    /// Returns whether `path` is a directory holding tracked files.
+    pub fn contains_directory(&self, path: &Path) -> bool {
+        self.directories.contains(path)
+    }
+}
+
+/// The revision checked out in a git repository.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GitInfo {
+    /// The name of the current branch, or `HEAD` when detached.
+    pub branch: String,
+    /// The abbreviated hash of the current commit.
+    pub commit: String,
+}
+
+impl GitInfo {
+    /// Queries the current branch and commit of the repository holding `root`
+    /// with `git rev-parse`.
+    ///
+    /// **Arguments**
+    ///
+    /// * `root` - A reference to the [`Path`] of a directory.
+    ///
+    /// **Returns**
+    ///
+    /// An [`Option<GitInfo>`]. Returns [`None`] if `root` is not inside a git
+    /// repository, if the repository has no commit yet, or if git could not be run.
+    pub fn query(root: &Path) -> Option<Self> {
+        let rev_parse = |args: &[&str]| {
+            run_git(root, args).map(|stdout| String::from_utf8_lossy(&stdout).trim().to_string())
+        };
+        Some(Self {
+            branch: rev_parse(&["rev-parse", "--abbrev-ref", "HEAD"])?,
+            commit: rev_parse(&["rev-parse", "--short", "HEAD"])?,
+        })
+    }
+}
+
+impl Display for GitInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}@{}", self.branch, self.commit)
+    }
+}
+
+/// Runs git with `args` in `root`.
+///
+/// **Returns**
+///
+/// The standard output of git, or [`None`] if it could not be run or failed.
+fn run_git(root: &Path, args: &[&str]) -> Option<Vec<u8>> {
+    let output = match Command::new("git").args(args).current_dir(root).output() {
+        Ok(output) => output,
+        Err(err) => {
+            Logger::debug(format!("Failed to run git: {}", err).as_str());
+            return None;
+        }
+    };
+    if !output.status.success() {
+        Logger::debug(
+            format!(
+                "git {} failed: {}",
+                args.join(" "),
+                String::from_utf8_lossy(&output.stderr).trim()
+            )
+            .as_str(),
+        );
+        return None;
+    }
+    Some(output.stdout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn git(dir: &Path, args: &[&str]) {
+        let status = Command::new("git")
+            .args(args)
+            .current_dir(dir)
+            .output()
+            .unwrap()
+            .status;
+        assert!(status.success(), "git {:?} failed", args);
+    }
+
+    #[test]
+    fn test_tracked_files() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        fs::create_dir_all(root.join("src/nested")).unwrap();
+        fs::create_dir_all(root.join("build")).unwrap();
+        fs::write(root.join("src/nested/main.rs"), "fn main() {}").unwrap();
+        fs::write(root.join("untracked.txt"), "").unwrap();
+        fs::write(root.join("build/artifact"), "").unwrap();
+        git(&root, &["init", "-q"]);
+        git(&root, &["add", "src/nested/main.rs"]);
+
+        let tracked = TrackedFiles::list(&root).unwrap();
+        assert!(tracked.contains_file(&root.join("src/nested/main.rs")));
+        assert!(!tracked.contains_file(&root.join("untracked.txt")));
+        assert!(tracked.contains_directory(&root.join("src")));
+        assert!(tracked.contains_directory(&root.join("src/nested")));
+        assert!(!tracked.contains_directory(&root.join("build")));
+    }
+
+    #[test]
+    fn test_git_info() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        assert_eq!(GitInfo::query(&root), None);
+
+        git(&root, &["init", "-q", "-b", "main"]);
+        // No commit yet
+        assert_eq!(GitInfo::query(&root), None);
+
+        fs::write(root.join("main.rs"), "fn main() {}").unwrap();
+        git(&root, &["add", "main.rs"]);
+        git(
+            &root,
+            &[
+                "-c",
+                "user.name=cunw",
+                "-c",
+                "user.email=cunw@example.com",
+                "commit",
+                "-q",
+                "-m",
+                "Initial commit",
+            ],
+        );
+        let git_info = GitInfo::query(&root).unwrap();
+        assert_eq!(git_info.branch, "main");
+        assert!(!git_info.commit.is_empty());
+        assert_eq!(git_info.to_string(), format!("main@{}", git_info.commit));
+    }
+}
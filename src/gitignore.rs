@@ -1,7 +1,14 @@
-use std::path::{Path, PathBuf};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
 
+use futures::{stream, StreamExt};
+/// Re-exported so that the rules given to [`GitIgnore::from_gitignore`] can be built
+/// with the same version of the `ignore` crate.
+pub use ignore::gitignore::{Gitignore, GitignoreBuilder};
 use ignore::{
-    gitignore::{Gitignore, GitignoreBuilder},
+    gitignore::{gitconfig_excludes_path, Glob},
     Match,
 };
 
@@ -10,6 +17,20 @@ use crate::{
     logger::Logger,
 };
 
+/// The name of the ignore files looked for by default.
+pub const DEFAULT_IGNORE_FILENAME: &str = ".gitignore";
+
+/// What the rules of a gitignore say about a path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IgnoreMatch {
+    /// No rule matches the path.
+    None,
+    /// The path is ignored by a pattern.
+    Ignore,
+    /// The path is explicitly re-included by a `!pattern`.
+    Whitelist,
+}
+
 /// Represents a `.gitignore` file and provides methods to check if paths are excluded.
 ///
 /// This struct encapsulates the logic for parsing and applying gitignore rules
@@ -22,68 +43,143 @@ pub struct GitIgnore {
 }
 
 impl GitIgnore {
-    /// Creates a [`GitignoreBuilder`] and determines the root directory for a given path.
+    /// Creates a new [`GitIgnore`] instance from a given path.
+    ///
+    /// This method attempts to create a [`GitIgnore`] instance from either a directory
+    /// containing ignore files or from a direct path to an ignore file.
     ///
     /// **Arguments**
     ///
     /// * `path` - A reference to a [`Path`] that points to either a directory containing
-    ///            a `.gitignore` file or directly to a `.gitignore` file.
+    ///   ignore files or directly to an ignore file.
+    /// * `filenames` - The names of the ignore files to look for in a directory (e.g.
+    ///   `.gitignore`). When several exist, they are merged, the rules of the last
+    ///   ones taking precedence.
     ///
     /// **Returns**
     ///
-    /// A tuple containing the [`GitignoreBuilder`] and the root [`PathBuf`].
-    fn builder_from(path: &Path) -> (GitignoreBuilder, PathBuf) {
-        let (root, gitignore) = {
-            if path.is_dir() {
-                (path, path.join(".gitignore"))
-            } else {
-                (
-                    path.parent().unwrap_or_else(|| Path::new("/")),
-                    path.to_path_buf(),
-                )
-            }
+    /// A [`Result`] containing an [`Option<GitIgnore>`]. Returns [`None`] if no ignore
+    /// file is found or if the path doesn't exist.
+    pub fn from(path: &Path, filenames: &[String]) -> Result<Option<Self>> {
+        let (root, sources) = if path.is_dir() {
+            let sources = filenames
+                .iter()
+                .map(|filename| path.join(filename))
+                .filter(|source| source.is_file())
+                .collect::<Vec<_>>();
+            (path, sources)
+        } else if path.exists() {
+            (
+                path.parent().unwrap_or_else(|| Path::new("/")),
+                vec![path.to_path_buf()],
+            )
+        } else {
+            return Ok(None);
         };
+        if sources.is_empty() {
+            return Ok(None);
+        }
+
         let mut builder = GitignoreBuilder::new(root);
-        builder.add(gitignore);
-        (builder, root.to_path_buf())
+        for source in &sources {
+            add_source(&mut builder, source)?;
+        }
+        let root = root.to_path_buf();
+        let gitignore = builder
+            .build()
+            .map_err(|err| CunwError::new(err.into()).with_file(path.to_path_buf()))?;
+
+        Logger::debug(&format!("Created GitIgnore from path: {:?}", path));
+        Logger::debug(&format!("Root directory: {:?}", root));
+
+        Ok(Some(Self {
+            gitignore,
+            path: path.to_path_buf(),
+            root,
+        }))
     }
 
-    /// Creates a new [`GitIgnore`] instance from a given path.
+    /// Wraps a [`Gitignore`] built by the caller, e.g. from rules stored somewhere else
+    /// than in ignore files, its patterns being relative to [`Gitignore::path`].
     ///
-    /// This method attempts to create a [`GitIgnore`] instance from either a directory
-    /// containing a `.gitignore` file or from a direct path to a `.gitignore` file.
+    /// ```
+    /// use cunw::gitignore::{GitIgnore, GitignoreBuilder};
+    /// use std::path::Path;
+    ///
+    /// let mut builder = GitignoreBuilder::new("/project");
+    /// builder.add_line(None, "*.secret").unwrap();
+    /// let gitignore = GitIgnore::from_gitignore(builder.build().unwrap());
+    /// assert!(gitignore.is_excluded(Path::new("/project/api.secret"), false));
+    /// ```
+    pub fn from_gitignore(gitignore: Gitignore) -> Self {
+        Self {
+            path: gitignore.path().to_path_buf(),
+            root: gitignore.path().to_path_buf(),
+            gitignore,
+        }
+    }
+
+    /// Returns the same rules, with their patterns relative to `root` instead.
+    pub(crate) fn with_root(mut self, root: PathBuf) -> Self {
+        self.root = root;
+        self
+    }
+
+    /// Creates a new [`GitIgnore`] instance for the root of the scanned directory.
+    ///
+    /// On top of the ignore files of the given directory, this merges the patterns
+    /// of the repository's `.git/info/exclude` file and of the user's global excludes file
+    /// (`core.excludesFile` from the git config, falling back to `$XDG_CONFIG_HOME/git/ignore`).
+    /// Like with git, the ignore files of the directory take precedence over
+    /// `.git/info/exclude`, which takes precedence over the global excludes file.
     ///
     /// **Arguments**
     ///
-    /// * `path` - A reference to a [`Path`] that points to either a directory containing
-    ///            a `.gitignore` file or directly to a `.gitignore` file.
+    /// * `path` - A reference to a [`Path`] that points to the root directory.
+    /// * `filenames` - The names of the ignore files to look for (e.g. `.gitignore`).
     ///
     /// **Returns**
     ///
-    /// A [`Result`] containing an [`Option<GitIgnore>`]. Returns [`None`] if no `.gitignore`
-    /// file is found or if the path doesn't exist.
-    pub fn from(path: &Path) -> Result<Option<Self>> {
-        if path.is_dir() {
-            let gitignore_path = path.join(".gitignore");
-            if !gitignore_path.exists() {
-                return Ok(None);
-            }
-        } else if !path.exists() {
+    /// A [`Result`] containing an [`Option<GitIgnore>`]. Returns [`None`] if none of
+    /// these files exist.
+    pub fn from_root(path: &Path, filenames: &[String]) -> Result<Option<Self>> {
+        Self::from_root_with_global_excludes(path, filenames, gitconfig_excludes_path())
+    }
+
+    /// Like [`GitIgnore::from_root`], with the given global excludes file instead of
+    /// the one of the user's git config.
+    fn from_root_with_global_excludes(
+        path: &Path,
+        filenames: &[String],
+        global_excludes: Option<PathBuf>,
+    ) -> Result<Option<Self>> {
+        // The rules of the last sources take precedence
+        let sources = global_excludes
+            .into_iter()
+            .chain([path.join(".git").join("info").join("exclude")])
+            .chain(filenames.iter().map(|filename| path.join(filename)))
+            .filter(|source| source.is_file())
+            .collect::<Vec<_>>();
+        if sources.is_empty() {
             return Ok(None);
         }
 
-        let (builder, root) = Self::builder_from(path);
+        let mut builder = GitignoreBuilder::new(path);
+        for source in &sources {
+            Logger::debug(&format!(
+                "Adding ignore file to root GitIgnore: {:?}",
+                source
+            ));
+            add_source(&mut builder, source)?;
+        }
         let gitignore = builder
             .build()
             .map_err(|err| CunwError::new(err.into()).with_file(path.to_path_buf()))?;
 
-        Logger::debug(&format!("Created GitIgnore from path: {:?}", path));
-        Logger::debug(&format!("Root directory: {:?}", root));
-
         Ok(Some(Self {
             gitignore,
             path: path.to_path_buf(),
-            root,
+            root: path.to_path_buf(),
         }))
     }
 
@@ -96,16 +192,53 @@ impl GitIgnore {
     /// **Arguments**
     ///
     /// * `path` - A reference to a [`Path`] to check against the gitignore rules.
+    /// * `is_dir` - Whether the path is a directory, which the directory-only patterns
+    ///   (e.g. `build/`) only match.
     ///
     /// **Returns**
     ///
     /// A boolean indicating whether the path should be excluded (`true`) or not (`false`).
-    pub fn is_excluded(&self, path: &Path) -> bool {
+    pub fn is_excluded(&self, path: &Path, is_dir: bool) -> bool {
+        self.matched(path, is_dir) == IgnoreMatch::Ignore
+    }
+
+    /// Returns what the gitignore rules say about a given path, telling apart the
+    /// paths that are explicitly re-included from the ones that are not mentioned.
+    ///
+    /// **Arguments**
+    ///
+    /// * `path` - A reference to a [`Path`] to check against the gitignore rules.
+    /// * `is_dir` - Whether the path is a directory.
+    ///
+    /// **Returns**
+    ///
+    /// The [`IgnoreMatch`] of the last rule matching the path or one of its parents.
+    pub fn matched(&self, path: &Path, is_dir: bool) -> IgnoreMatch {
+        match self.matched_rule(path, is_dir) {
+            Match::None => IgnoreMatch::None,
+            Match::Ignore(_) => IgnoreMatch::Ignore,
+            Match::Whitelist(_) => IgnoreMatch::Whitelist,
+        }
+    }
+
+    /// Returns the last rule matching the path or one of its parents, if any.
+    ///
+    /// The patterns are matched against the path relative to the directory of the
+    /// gitignore (whatever the root of the walk), so an absolute path outside of
+    /// that directory, or the directory itself, never matches. The parents are always
+    /// matched as directories.
+    fn matched_rule(&self, path: &Path, is_dir: bool) -> Match<&Glob> {
         let relative_path = if path.is_absolute() {
-            path.strip_prefix(&self.root).unwrap_or(path)
+            match path.strip_prefix(&self.root) {
+                Ok(relative_path) => relative_path,
+                Err(_) => return Match::None,
+            }
         } else {
             path
         };
+        if relative_path.as_os_str().is_empty() {
+            return Match::None;
+        }
 
         Logger::debug(&format!(
             "Checking if path is excluded: {:?}",
@@ -114,22 +247,63 @@ impl GitIgnore {
 
         let match_result = self
             .gitignore
-            .matched_path_or_any_parents(relative_path, false);
+            .matched_path_or_any_parents(relative_path, is_dir);
 
         match match_result {
-            Match::None => {
-                Logger::debug("Path is not excluded (no match)");
-                false
-            }
-            Match::Ignore(_) => {
-                Logger::debug("Path is excluded (ignore match)");
-                true
-            }
-            Match::Whitelist(_) => {
-                Logger::debug("Path is not excluded (whitelist match)");
-                false
-            }
+            Match::None => Logger::debug("Path is not excluded (no match)"),
+            Match::Ignore(_) => Logger::debug("Path is excluded (ignore match)"),
+            Match::Whitelist(_) => Logger::debug("Path is not excluded (whitelist match)"),
         }
+        match_result
+    }
+
+    /// Checks if a given path should be excluded based on layered gitignores.
+    ///
+    /// Like git, the rules of the gitignores closer to the path take precedence, so a
+    /// `!pattern` in a child directory re-includes a path excluded by an ancestor.
+    ///
+    /// **Arguments**
+    ///
+    /// * `gitignores` - The gitignores that apply to the path, nearest first.
+    /// * `path` - A reference to a [`Path`] to check against the gitignore rules.
+    /// * `is_dir` - Whether the path is a directory.
+    ///
+    /// **Returns**
+    ///
+    /// A boolean indicating whether the path should be excluded (`true`) or not (`false`).
+    pub fn is_excluded_by_any<'a>(
+        gitignores: impl IntoIterator<Item = &'a GitIgnore>,
+        path: &Path,
+        is_dir: bool,
+    ) -> bool {
+        Self::excluding_rule(gitignores, path, is_dir).is_some()
+    }
+
+    /// Returns the rule excluding a path based on layered gitignores, if any, along
+    /// with the ignore file it comes from (e.g. `*.log (/project/.gitignore)`).
+    ///
+    /// **Arguments**
+    ///
+    /// * `gitignores` - The gitignores that apply to the path, nearest first.
+    /// * `path` - A reference to a [`Path`] to check against the gitignore rules.
+    /// * `is_dir` - Whether the path is a directory.
+    pub fn excluding_rule<'a>(
+        gitignores: impl IntoIterator<Item = &'a GitIgnore>,
+        path: &Path,
+        is_dir: bool,
+    ) -> Option<String> {
+        gitignores
+            .into_iter()
+            .map(|gitignore| (gitignore, gitignore.matched_rule(path, is_dir)))
+            .find(|(_, matched)| !matched.is_none())
+            .and_then(|(gitignore, matched)| match matched {
+                Match::Ignore(glob) => Some(format!(
+                    "{} ({})",
+                    glob.original(),
+                    glob.from().unwrap_or(&gitignore.path).display()
+                )),
+                _ => None,
+            })
     }
 }
 
@@ -139,10 +313,187 @@ impl PartialEq for GitIgnore {
     }
 }
 
+/// Adds the rules of the ignore file `source` to `builder`.
+///
+/// The invalid lines of the file are skipped, but a file that cannot be read is an error.
+fn add_source(builder: &mut GitignoreBuilder, source: &Path) -> Result<()> {
+    match builder.add(source) {
+        Some(err) if err.io_error().is_some() => {
+            Err(CunwError::new(err.into()).with_file(source.to_path_buf()))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// The gitignores of the directories of a tree, keyed by directory.
+///
+/// Reading the ignore files is a blocking disk read per directory, so
+/// [`GitIgnoreCache::prefetch`] reads them concurrently ahead of the walk, which
+/// then only looks them up.
+#[derive(Debug, Default)]
+pub struct GitIgnoreCache {
+    gitignores: HashMap<PathBuf, Option<GitIgnore>>,
+}
+
+impl GitIgnoreCache {
+    /// Loads the gitignores of `root` and of its directories, level by level, the
+    /// ignore files of a level being read concurrently on the blocking threads.
+    ///
+    /// The directories that are excluded by `is_excluded` or by the gitignores loaded
+    /// so far are not entered, nor are the ones deeper than `max_depth` (the root
+    /// being at depth 0) or reached through a symbolic link. The gitignores that fail
+    /// to load are not cached, so that [`GitIgnoreCache::get`] reports the error.
+    ///
+    /// **Arguments**
+    ///
+    /// * `root` - The root of the walk, whose gitignore is loaded with [`GitIgnore::from_root`].
+    /// * `filenames` - The names of the ignore files to look for (e.g. `.gitignore`).
+    /// * `max_depth` - The maximum depth of the walk, if any.
+    /// * `concurrency` - The maximum number of directories whose ignore files are read at once.
+    /// * `is_excluded` - Whether a directory is excluded regardless of the gitignores.
+    pub async fn prefetch(
+        root: &Path,
+        filenames: &[String],
+        max_depth: Option<usize>,
+        concurrency: usize,
+        is_excluded: impl Fn(&Path) -> bool,
+    ) -> Self {
+        let mut cache = Self::default();
+        let mut level = vec![root.to_path_buf()];
+        let mut depth = 0;
+        while !level.is_empty() {
+            // The entries of a directory at `max_depth` are not walked
+            let list_subdirectories = max_depth.is_none_or(|max_depth| depth + 1 < max_depth);
+            let loaded = stream::iter(level)
+                .map(|dir| {
+                    let filenames = filenames.to_vec();
+                    let is_root = dir == root;
+                    tokio::task::spawn_blocking(move || {
+                        let gitignore = if is_root {
+                            GitIgnore::from_root(&dir, &filenames)
+                        } else {
+                            GitIgnore::from(&dir, &filenames)
+                        };
+                        let subdirectories = if list_subdirectories {
+                            Self::subdirectories(&dir)
+                        } else {
+                            Vec::new()
+                        };
+                        (dir, gitignore, subdirectories)
+                    })
+                })
+                .buffer_unordered(concurrency)
+                .collect::<Vec<_>>()
+                .await;
+
+            let mut next_level = Vec::new();
+            for (dir, gitignore, subdirectories) in loaded.into_iter().flatten() {
+                match gitignore {
+                    Ok(gitignore) => {
+                        cache.gitignores.insert(dir, gitignore);
+                    }
+                    Err(err) => {
+                        Logger::debug(format!("Failed to prefetch gitignore: {}", err).as_str());
+                        continue;
+                    }
+                }
+                next_level.extend(subdirectories);
+            }
+            // Only now are the gitignores of the parents of the next level all loaded
+            next_level.retain(|dir| !is_excluded(dir) && !cache.is_ignored(root, dir));
+            level = next_level;
+            depth += 1;
+        }
+        cache
+    }
+
+    /// Returns the gitignore of `dir`, loading (and caching) it if it was not prefetched.
+    ///
+    /// If the ignore files fail to load, the error is returned and `dir` is cached as
+    /// having no gitignore, so that the next calls do not report it again.
+    ///
+    /// **Arguments**
+    ///
+    /// * `dir` - The directory whose ignore files are looked for.
+    /// * `filenames` - The names of the ignore files to look for (e.g. `.gitignore`).
+    /// * `is_root` - Whether `dir` is the root of the walk, see [`GitIgnore::from_root`].
+    pub fn get(
+        &mut self,
+        dir: &Path,
+        filenames: &[String],
+        is_root: bool,
+    ) -> Result<Option<GitIgnore>> {
+        if let Some(gitignore) = self.gitignores.get(dir) {
+            return Ok(gitignore.clone());
+        }
+        let gitignore = if is_root {
+            GitIgnore::from_root(dir, filenames)
+        } else {
+            GitIgnore::from(dir, filenames)
+        };
+        let gitignore = match gitignore {
+            Ok(gitignore) => gitignore,
+            Err(err) => {
+                // Only reported once, the directory is then treated as having none
+                self.gitignores.insert(dir.to_path_buf(), None);
+                return Err(err);
+            }
+        };
+        self.gitignores.insert(dir.to_path_buf(), gitignore.clone());
+        Ok(gitignore)
+    }
+
+    /// Forgets the cached gitignores that a change of `path` may affect, so that the next
+    /// [`GitIgnoreCache::get`] reads them again: the ones of its directory if it is an
+    /// ignore file (or of `root` for `.git/info/exclude`), and the ones of the
+    /// directories under it, in case it was moved or removed.
+    ///
+    /// **Arguments**
+    ///
+    /// * `root` - The root of the walk, whose gitignore is loaded with [`GitIgnore::from_root`].
+    /// * `path` - The path that changed.
+    /// * `filenames` - The names of the ignore files to look for (e.g. `.gitignore`).
+    pub fn invalidate(&mut self, root: &Path, path: &Path, filenames: &[String]) {
+        let is_ignore_file = path
+            .file_name()
+            .is_some_and(|name| filenames.iter().any(|filename| name == filename.as_str()));
+        let dir = if path.ends_with(Path::new(".git").join("info").join("exclude")) {
+            Some(root)
+        } else {
+            path.parent().filter(|_| is_ignore_file)
+        };
+        self.gitignores
+            .retain(|cached, _| Some(cached.as_path()) != dir && !cached.starts_with(path));
+    }
+
+    /// Returns whether the directory `path` is excluded by the cached gitignores of its
+    /// ancestors, up to `root`.
+    fn is_ignored(&self, root: &Path, path: &Path) -> bool {
+        let gitignores = path
+            .ancestors()
+            .skip(1)
+            .take_while(|ancestor| ancestor.starts_with(root))
+            .filter_map(|ancestor| self.gitignores.get(ancestor).and_then(Option::as_ref));
+        GitIgnore::is_excluded_by_any(gitignores, path, true)
+    }
+
+    /// Returns the directories directly inside `dir`, without following symbolic links.
+    fn subdirectories(dir: &Path) -> Vec<PathBuf> {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return Vec::new();
+        };
+        entries
+            .flatten()
+            .filter(|entry| entry.file_type().is_ok_and(|file_type| file_type.is_dir()))
+            .map(|entry| entry.path())
+            .collect()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::fs::File;
+    use std::fs::{self, File};
     use std::io::Write;
     use tempfile::TempDir;
 
@@ -163,15 +514,45 @@ mod tests {
         gitignore_path
     }
 
+    fn default_filenames() -> Vec<String> {
+        vec![DEFAULT_IGNORE_FILENAME.to_string()]
+    }
+
     #[test]
     fn test_gitignore_from_dir() {
         let dir = TempDir::new().unwrap();
         create_gitignore(&dir, "*.txt\n!important.txt");
 
-        let gitignore = GitIgnore::from(dir.path()).unwrap().unwrap();
-        assert!(gitignore.is_excluded(Path::new("file.txt")));
-        assert!(!gitignore.is_excluded(Path::new("important.txt")));
-        assert!(!gitignore.is_excluded(Path::new("file.rs")));
+        let gitignore = GitIgnore::from(dir.path(), &default_filenames())
+            .unwrap()
+            .unwrap();
+        assert!(gitignore.is_excluded(Path::new("file.txt"), false));
+        assert!(!gitignore.is_excluded(Path::new("important.txt"), false));
+        assert!(!gitignore.is_excluded(Path::new("file.rs"), false));
+    }
+
+    #[test]
+    fn test_gitignore_from_dir_with_several_filenames() {
+        let dir = TempDir::new().unwrap();
+        create_gitignore(&dir, "*.txt\n*.log");
+        fs::write(dir.path().join(".cunwignore"), "!keep.txt\n*.md\n").unwrap();
+        let filenames = vec![".gitignore".to_string(), ".cunwignore".to_string()];
+
+        let gitignore = GitIgnore::from(dir.path(), &filenames).unwrap().unwrap();
+        assert!(gitignore.is_excluded(Path::new("file.txt"), false));
+        assert!(gitignore.is_excluded(Path::new("error.log"), false));
+        assert!(gitignore.is_excluded(Path::new("README.md"), false));
+        // The last ignore file takes precedence
+        assert!(!gitignore.is_excluded(Path::new("keep.txt"), false));
+
+        let only_cunwignore = vec![".cunwignore".to_string()];
+        let gitignore = GitIgnore::from(dir.path(), &only_cunwignore)
+            .unwrap()
+            .unwrap();
+        assert!(!gitignore.is_excluded(Path::new("file.txt"), false));
+        assert!(GitIgnore::from(dir.path(), &[".ignore".to_string()])
+            .unwrap()
+            .is_none());
     }
 
     #[test]
@@ -179,11 +560,264 @@ mod tests {
         let dir = TempDir::new().unwrap();
         let gitignore_path = create_gitignore(&dir, "*.log\ntemp/\n!temp/keep.txt");
 
-        let gitignore = GitIgnore::from(&gitignore_path).unwrap().unwrap();
-        assert!(gitignore.is_excluded(Path::new("error.log")));
-        assert!(gitignore.is_excluded(Path::new("temp/file.txt")));
-        assert!(!gitignore.is_excluded(Path::new("temp/keep.txt")));
-        assert!(!gitignore.is_excluded(Path::new("src/main.rs")));
+        let gitignore = GitIgnore::from(&gitignore_path, &[]).unwrap().unwrap();
+        assert!(gitignore.is_excluded(Path::new("error.log"), false));
+        assert!(gitignore.is_excluded(Path::new("temp/file.txt"), false));
+        assert!(!gitignore.is_excluded(Path::new("temp/keep.txt"), false));
+        assert!(!gitignore.is_excluded(Path::new("src/main.rs"), false));
+    }
+
+    #[test]
+    fn test_gitignore_from_root_merges_info_exclude() {
+        let dir = TempDir::new().unwrap();
+        create_gitignore(&dir, "*.log");
+        fs::create_dir_all(dir.path().join(".git/info")).unwrap();
+        fs::write(dir.path().join(".git/info/exclude"), "*.secret\n").unwrap();
+
+        let gitignore =
+            GitIgnore::from_root_with_global_excludes(dir.path(), &default_filenames(), None)
+                .unwrap()
+                .unwrap();
+        assert!(gitignore.is_excluded(Path::new("error.log"), false));
+        assert!(gitignore.is_excluded(Path::new("api.secret"), false));
+        assert!(!gitignore.is_excluded(Path::new("src/main.rs"), false));
+    }
+
+    #[test]
+    fn test_gitignore_from_root_without_gitignore() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir_all(dir.path().join(".git/info")).unwrap();
+        fs::write(dir.path().join(".git/info/exclude"), "*.secret\n").unwrap();
+
+        let gitignore =
+            GitIgnore::from_root_with_global_excludes(dir.path(), &default_filenames(), None)
+                .unwrap()
+                .unwrap();
+        assert!(gitignore.is_excluded(Path::new("api.secret"), false));
+    }
+
+    #[test]
+    fn test_gitignore_from_root_precedence() {
+        let dir = TempDir::new().unwrap();
+        let global_excludes = dir.path().join("global-ignore");
+        fs::write(&global_excludes, "*.log\n*.tmp\n!keep.secret\n").unwrap();
+        fs::create_dir_all(dir.path().join(".git/info")).unwrap();
+        fs::write(
+            dir.path().join(".git/info/exclude"),
+            "*.secret\n!notes.tmp\n",
+        )
+        .unwrap();
+        create_gitignore(&dir, "!important.log\n!debug.secret");
+
+        let gitignore = GitIgnore::from_root_with_global_excludes(
+            dir.path(),
+            &default_filenames(),
+            Some(global_excludes),
+        )
+        .unwrap()
+        .unwrap();
+        // The .gitignore wins over .git/info/exclude and the global excludes
+        assert!(!gitignore.is_excluded(Path::new("important.log"), false));
+        assert!(!gitignore.is_excluded(Path::new("debug.secret"), false));
+        // .git/info/exclude wins over the global excludes
+        assert!(!gitignore.is_excluded(Path::new("notes.tmp"), false));
+        assert!(gitignore.is_excluded(Path::new("keep.secret"), false));
+        assert!(gitignore.is_excluded(Path::new("error.log"), false));
+        assert!(gitignore.is_excluded(Path::new("cache.tmp"), false));
+    }
+
+    #[test]
+    fn test_gitignore_from_root_reads_global_excludes() {
+        // The environment is shared by the tests running in parallel, so the check
+        // runs in a child process with its own HOME and XDG_CONFIG_HOME
+        let home = TempDir::new().unwrap();
+        let config = home.path().join(".config");
+        fs::create_dir_all(config.join("git")).unwrap();
+        fs::write(config.join("git/ignore"), "*.log\n").unwrap();
+
+        let output = std::process::Command::new(std::env::current_exe().unwrap())
+            .args([
+                "--exact",
+                "gitignore::tests::global_excludes_in_child_process",
+                "--ignored",
+                "--nocapture",
+            ])
+            .env("HOME", home.path())
+            .env("XDG_CONFIG_HOME", &config)
+            .env("CUNW_TEST_GLOBAL_EXCLUDES", "1")
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(output.status.success(), "{}", stdout);
+        assert!(stdout.contains("1 passed"), "{}", stdout);
+    }
+
+    #[test]
+    #[ignore = "run by test_gitignore_from_root_reads_global_excludes"]
+    fn global_excludes_in_child_process() {
+        // Only meaningful with the environment set by the parent test
+        if std::env::var_os("CUNW_TEST_GLOBAL_EXCLUDES").is_none() {
+            return;
+        }
+        let dir = TempDir::new().unwrap();
+        create_gitignore(&dir, "!important.log");
+
+        let gitignore = GitIgnore::from_root(dir.path(), &default_filenames())
+            .unwrap()
+            .unwrap();
+        assert!(gitignore.is_excluded(Path::new("error.log"), false));
+        // The negation of the .gitignore beats the global pattern
+        assert!(!gitignore.is_excluded(Path::new("important.log"), false));
+    }
+
+    #[test]
+    fn test_gitignore_layered_negation() {
+        let dir = TempDir::new().unwrap();
+        create_gitignore(&dir, "*.log");
+        fs::create_dir_all(dir.path().join("sub")).unwrap();
+        fs::write(dir.path().join("sub/.gitignore"), "!keep.log\n").unwrap();
+
+        let root = GitIgnore::from(dir.path(), &default_filenames())
+            .unwrap()
+            .unwrap();
+        let sub = GitIgnore::from(&dir.path().join("sub"), &default_filenames())
+            .unwrap()
+            .unwrap();
+        let gitignores = [sub, root];
+        assert!(!GitIgnore::is_excluded_by_any(
+            &gitignores,
+            &dir.path().join("sub/keep.log"),
+            false
+        ));
+        assert!(GitIgnore::is_excluded_by_any(
+            &gitignores,
+            &dir.path().join("sub/other.log"),
+            false
+        ));
+        assert!(!GitIgnore::is_excluded_by_any(
+            &gitignores,
+            &dir.path().join("sub/main.rs"),
+            false
+        ));
+    }
+
+    #[test]
+    fn test_gitignore_nested_relative_to_own_directory() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        create_gitignore(&dir, "/build\n*.log\n");
+        fs::create_dir_all(root.join("a/b/c/d/e")).unwrap();
+        fs::write(root.join("a/b/.gitignore"), "/c/d/generated\ne/\n").unwrap();
+
+        let root_gitignore =
+            GitIgnore::from_root_with_global_excludes(&root, &default_filenames(), None)
+                .unwrap()
+                .unwrap();
+        let nested = GitIgnore::from(&root.join("a/b"), &default_filenames())
+            .unwrap()
+            .unwrap();
+        let gitignores = [nested.clone(), root_gitignore.clone()];
+        let is_excluded =
+            |path: &str| GitIgnore::is_excluded_by_any(&gitignores, &root.join(path), false);
+
+        // A root-level pattern applies to a deeply nested file
+        assert!(is_excluded("a/b/c/d/e/deep.log"));
+        // A pattern anchored to the root only matches at the root
+        assert!(is_excluded("build/out.rs"));
+        assert!(!is_excluded("a/b/c/build/out.rs"));
+        // The nested patterns are relative to their own directory
+        assert!(is_excluded("a/b/c/d/generated"));
+        assert!(is_excluded("a/b/c/d/e/main.rs"));
+        assert!(!is_excluded("c/d/generated"));
+        assert!(!is_excluded("a/b/c/d/f/main.rs"));
+        // A path outside of the directory of a gitignore is not matched by it
+        assert!(!nested.is_excluded(&root.join("e/main.rs"), false));
+        assert!(!nested.is_excluded(&root.join("a/b"), true));
+        assert!(!root_gitignore.is_excluded(Path::new("/elsewhere/build"), false));
+    }
+
+    #[test]
+    fn test_gitignore_matched() {
+        let dir = TempDir::new().unwrap();
+        create_gitignore(&dir, "*.log\n!keep.log");
+        let gitignore = GitIgnore::from(dir.path(), &default_filenames())
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(
+            gitignore.matched(&dir.path().join("debug.log"), false),
+            IgnoreMatch::Ignore
+        );
+        assert_eq!(
+            gitignore.matched(&dir.path().join("keep.log"), false),
+            IgnoreMatch::Whitelist
+        );
+        assert_eq!(
+            gitignore.matched(&dir.path().join("main.rs"), false),
+            IgnoreMatch::None
+        );
+        assert!(!gitignore.is_excluded(&dir.path().join("keep.log"), false));
+    }
+
+    #[tokio::test]
+    async fn test_gitignore_cache_prefetch() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        create_gitignore(&dir, "ignored");
+        for subdir in ["web/dist", "ignored", "excluded", "deep/a/b"] {
+            fs::create_dir_all(root.join(subdir)).unwrap();
+        }
+        for subdir in ["web", "web/dist", "ignored", "excluded", "deep/a/b"] {
+            fs::write(root.join(subdir).join(".gitignore"), "*.log\n").unwrap();
+        }
+        fs::write(root.join("web/.gitignore"), "dist\n").unwrap();
+
+        let cache = GitIgnoreCache::prefetch(&root, &default_filenames(), Some(3), 2, |dir| {
+            dir.ends_with("excluded")
+        })
+        .await;
+        let prefetched = |path: &str| cache.gitignores.contains_key(&root.join(path));
+        assert!(prefetched(""));
+        assert!(prefetched("web"));
+        // Directories without ignore files are cached too
+        assert!(prefetched("deep/a"));
+        assert_eq!(cache.gitignores[&root.join("deep/a")], None);
+        // Ignored by a gitignore, excluded, or too deep
+        assert!(!prefetched("web/dist"));
+        assert!(!prefetched("ignored"));
+        assert!(!prefetched("excluded"));
+        assert!(!prefetched("deep/a/b"));
+
+        // The directories that were not prefetched are loaded on demand
+        let mut cache = cache;
+        let gitignore = cache
+            .get(&root.join("deep/a/b"), &default_filenames(), false)
+            .unwrap()
+            .unwrap();
+        assert!(gitignore.is_excluded(&root.join("deep/a/b/debug.log"), false));
+        assert!(cache.gitignores.contains_key(&root.join("deep/a/b")));
+    }
+
+    #[test]
+    fn test_gitignore_excluding_rule() {
+        let dir = TempDir::new().unwrap();
+        let gitignore_path = create_gitignore(&dir, "*.log\n!keep.log\n");
+        let gitignore = GitIgnore::from(dir.path(), &default_filenames())
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(
+            GitIgnore::excluding_rule([&gitignore], &dir.path().join("debug.log"), false),
+            Some(format!("*.log ({})", gitignore_path.display()))
+        );
+        assert_eq!(
+            GitIgnore::excluding_rule([&gitignore], &dir.path().join("keep.log"), false),
+            None
+        );
+        assert_eq!(
+            GitIgnore::excluding_rule([&gitignore], &dir.path().join("main.rs"), false),
+            None
+        );
     }
 
     #[test]
@@ -191,10 +825,24 @@ mod tests {
         let dir = TempDir::new().unwrap();
         let gitignore_path = create_gitignore(&dir, "/root.txt\n/src/*.rs\n!/src/main.rs");
 
-        let gitignore = GitIgnore::from(&gitignore_path).unwrap().unwrap();
-        assert!(gitignore.is_excluded(Path::new("root.txt")));
-        assert!(gitignore.is_excluded(Path::new("src/lib.rs")));
-        assert!(!gitignore.is_excluded(Path::new("src/main.rs")));
-        assert!(!gitignore.is_excluded(Path::new("doc/root.txt")));
+        let gitignore = GitIgnore::from(&gitignore_path, &[]).unwrap().unwrap();
+        assert!(gitignore.is_excluded(Path::new("root.txt"), false));
+        assert!(gitignore.is_excluded(Path::new("src/lib.rs"), false));
+        assert!(!gitignore.is_excluded(Path::new("src/main.rs"), false));
+        assert!(!gitignore.is_excluded(Path::new("doc/root.txt"), false));
+    }
+
+    #[test]
+    fn test_gitignore_directory_only_patterns() {
+        let dir = TempDir::new().unwrap();
+        let gitignore_path = create_gitignore(&dir, "build/\n");
+
+        let gitignore = GitIgnore::from(&gitignore_path, &[]).unwrap().unwrap();
+        assert!(gitignore.is_excluded(Path::new("build"), true));
+        assert!(gitignore.is_excluded(Path::new("src/build"), true));
+        assert!(!gitignore.is_excluded(Path::new("build"), false));
+        assert!(!gitignore.is_excluded(Path::new("src/build"), false));
+        // The content of the directory is excluded along with it
+        assert!(gitignore.is_excluded(Path::new("build/out.rs"), false));
     }
 }
@@ -0,0 +1,86 @@
+use std::path::Path;
+
+/// Programming languages recognized by cunw.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Language {
+    Rust,
+    Python,
+    JavaScript,
+    TypeScript,
+    Go,
+    C,
+    Cpp,
+    Java,
+    Shell,
+    Toml,
+    Yaml,
+    Markdown,
+}
+
+impl Language {
+    /// Returns the human readable name of the language.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Language::Rust => "Rust",
+            Language::Python => "Python",
+            Language::JavaScript => "JavaScript",
+            Language::TypeScript => "TypeScript",
+            Language::Go => "Go",
+            Language::C => "C",
+            Language::Cpp => "C++",
+            Language::Java => "Java",
+            Language::Shell => "Shell",
+            Language::Toml => "TOML",
+            Language::Yaml => "YAML",
+            Language::Markdown => "Markdown",
+        }
+    }
+
+    /// Returns the identifier of the language for syntax highlighters, as used in
+    /// `language-*` classes (e.g. `language-rust`).
+    pub fn identifier(&self) -> &'static str {
+        match self {
+            Language::Rust => "rust",
+            Language::Python => "python",
+            Language::JavaScript => "javascript",
+            Language::TypeScript => "typescript",
+            Language::Go => "go",
+            Language::C => "c",
+            Language::Cpp => "cpp",
+            Language::Java => "java",
+            Language::Shell => "bash",
+            Language::Toml => "toml",
+            Language::Yaml => "yaml",
+            Language::Markdown => "markdown",
+        }
+    }
+}
+
+/// Returns the language of a file based on its extension, if it is recognized.
+///
+/// # Arguments
+///
+/// * `path` - A reference to the [`Path`] of the file.
+///
+/// # Returns
+///
+/// * `Some(language)` if the extension of the file is associated with a language.
+/// * `None` otherwise.
+pub fn language_for_path(path: &Path) -> Option<Language> {
+    let extension = path.extension()?.to_str()?;
+    match extension {
+        "rs" => Some(Language::Rust),
+        "py" | "pyi" => Some(Language::Python),
+        "js" | "jsx" | "mjs" | "cjs" => Some(Language::JavaScript),
+        "ts" | "tsx" | "mts" | "cts" => Some(Language::TypeScript),
+        "go" => Some(Language::Go),
+        "c" | "h" => Some(Language::C),
+        "cc" | "cpp" | "cxx" | "hh" | "hpp" | "hxx" => Some(Language::Cpp),
+        "java" => Some(Language::Java),
+        "sh" | "bash" | "zsh" => Some(Language::Shell),
+        "toml" => Some(Language::Toml),
+        "yml" | "yaml" => Some(Language::Yaml),
+        "md" | "markdown" => Some(Language::Markdown),
+        _ => None,
+    }
+}
@@ -0,0 +1,43 @@
+//! Generates a single file holding the structure and the content of a directory,
+//! to give context to a LLM.
+//!
+//! The `cunw` binary is a thin wrapper around [`CodebaseBuilder`], which walks a
+//! directory (honoring its ignore files) into a [`Codebase`] that can be rendered in
+//! the supported output formats.
+//!
+//! ```no_run
+//! use cunw::CodebaseBuilder;
+//!
+//! # #[tokio::main]
+//! # async fn main() -> cunw::error::Result<()> {
+//! let codebase = CodebaseBuilder::new()
+//!     .content_transform(Box::new(|_path, content| content.replace("hunter2", "[redacted]")))
+//!     .build(".".into())
+//!     .await?;
+//! println!("{} files", codebase.stats().files);
+//! println!("{}", codebase.try_to_string()?);
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod archive;
+pub mod args;
+pub mod codebase;
+pub mod comment;
+pub mod config;
+pub mod error;
+pub mod git;
+pub mod gitignore;
+pub mod language;
+pub mod logger;
+pub mod os;
+pub mod presets;
+pub mod secrets;
+#[cfg(feature = "signatures")]
+pub mod signatures;
+pub mod tree;
+pub mod utils;
+pub mod watch;
+
+pub use codebase::{item::CodebaseItem, Codebase, CodebaseBuilder};
+pub use gitignore::GitIgnore;
@@ -1,18 +1,87 @@
-use std::panic::Location;
+use std::{
+    fs::File,
+    io::{LineWriter, Write},
+    panic::Location,
+    sync::{Mutex, OnceLock},
+};
 
+use chrono::{SecondsFormat, Utc};
+use clap::ValueEnum;
 use colored::{ColoredString, Colorize};
-use log::{debug, error, info, trace, warn, Level};
+use log::{log, Level, LevelFilter};
 use terminal_emoji::Emoji;
 
+use crate::utils;
+
 pub struct Logger;
 
+/// The format of the logs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+    /// Colored, with an emoji for the level.
+    #[default]
+    Human,
+    /// One JSON object per line, with the `timestamp`, the `level`, the `message` and
+    /// its location (`file`, `line` and `column`).
+    Json,
+}
+
+/// The format of the logs, set once by [`Logger::set_format`].
+static FORMAT: OnceLock<LogFormat> = OnceLock::new();
+
 pub const LOCATION_WIDTH: usize = 40;
 pub const LEVEL_WIDTH: usize = 3;
 
 const CRATE_NAME: &str = env!("CARGO_PKG_NAME");
+/// The minimum level of the logs written to the log file, whatever the verbosity.
+const LOG_FILE_LEVEL: LevelFilter = LevelFilter::Debug;
 
 impl Logger {
     pub fn init(verbosity: Option<log::LevelFilter>) {
+        Self::init_with_log_file(verbosity, None);
+    }
+
+    /// Like [`Logger::init`], also writing the logs to `log_file`, without colors and
+    /// with a timestamp. The log file gets the debug logs even when the verbosity is
+    /// lower, so that stderr stays clean while the file keeps a debug trace.
+    ///
+    /// Each log is written as a whole line under a lock, so the logs of concurrent
+    /// tasks are not interleaved.
+    pub fn init_with_log_file(verbosity: Option<log::LevelFilter>, log_file: Option<File>) {
+        let Some(log_file) = log_file else {
+            return Self::init_stderr(verbosity);
+        };
+        let log_file = Mutex::new(LineWriter::new(log_file));
+        let indent = format!("\n{}", " ".repeat(LEVEL_WIDTH + LOCATION_WIDTH));
+        let mut builder = env_logger::builder();
+        builder.format(move |buf, record| {
+            let message = record.args().to_string();
+            if let Ok(mut log_file) = log_file.lock() {
+                // A log that cannot be written to the file is still written to stderr
+                let _ = match FORMAT.get().copied().unwrap_or_default() {
+                    LogFormat::Human => writeln!(
+                        log_file,
+                        "{} {}",
+                        chrono::Local::now().format("%Y-%m-%dT%H:%M:%S%.3f"),
+                        utils::strip_ansi_escapes(&message)
+                    ),
+                    // Already timestamped
+                    LogFormat::Json => writeln!(log_file, "{}", message),
+                };
+            }
+            if verbosity.is_none_or(|verbosity| record.level() <= verbosity) {
+                writeln!(buf, "{}", message.replace('\n', &indent))?;
+            }
+            Ok(())
+        });
+
+        if let Some(verbosity) = verbosity {
+            builder.filter_module(CRATE_NAME, verbosity.max(LOG_FILE_LEVEL));
+        }
+        let _ = builder.try_init();
+    }
+
+    fn init_stderr(verbosity: Option<log::LevelFilter>) {
         let mut builder = env_logger::builder();
         builder
             .format_timestamp(None)
@@ -27,6 +96,12 @@ impl Logger {
         let _ = builder.try_init();
     }
 
+    /// Sets the format of the logs, [`LogFormat::Human`] by default. Only the first
+    /// call has an effect, and it should happen before the first log.
+    pub fn set_format(format: LogFormat) {
+        let _ = FORMAT.set(format);
+    }
+
     fn format_location(l: &Location<'static>) -> ColoredString {
         let file = l.file();
         let line = l.line();
@@ -45,54 +120,79 @@ impl Logger {
             Level::Trace => format!("{}{}", Emoji::new("🔬", "T"), padding).purple(),
         }
     }
+    fn format_message(level: Level, message: &str) -> ColoredString {
+        match level {
+            Level::Error => message.red(),
+            Level::Warn => message.yellow(),
+            Level::Info => message.green(),
+            Level::Debug => message.blue(),
+            Level::Trace => message.purple(),
+        }
+    }
+    /// Formats a log as a single line JSON object.
+    fn format_json(level: Level, location: &Location<'static>, message: &str) -> String {
+        serde_json::json!({
+            "timestamp": Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+            "level": level.as_str().to_lowercase(),
+            "message": message,
+            "file": location.file(),
+            "line": location.line(),
+            "column": location.column(),
+        })
+        .to_string()
+    }
+    fn log(level: Level, location: &Location<'static>, message: &str) {
+        // Avoid formatting the logs that are filtered out
+        if !log::log_enabled!(level) {
+            return;
+        }
+        match FORMAT.get().copied().unwrap_or_default() {
+            LogFormat::Human => log!(
+                level,
+                "{} {} {}",
+                Self::format_location(location),
+                Self::format_level(level),
+                Self::format_message(level, message),
+            ),
+            LogFormat::Json => log!(level, "{}", Self::format_json(level, location, message)),
+        }
+    }
     #[track_caller]
     pub fn trace(message: &str) {
-        let location = Location::caller();
-        trace!(
-            "{} {} {}",
-            Self::format_location(&location),
-            Self::format_level(Level::Trace),
-            message.purple(),
-        );
+        Self::log(Level::Trace, Location::caller(), message);
     }
     #[track_caller]
     pub fn debug(message: &str) {
-        let location = Location::caller();
-        debug!(
-            "{} {} {}",
-            Self::format_location(&location),
-            Self::format_level(Level::Debug),
-            message.blue(),
-        );
+        Self::log(Level::Debug, Location::caller(), message);
     }
     #[track_caller]
     pub fn info(message: &str) {
-        let location = Location::caller();
-        info!(
-            "{} {} {}",
-            Self::format_location(&location),
-            Self::format_level(Level::Info),
-            message.green(),
-        );
+        Self::log(Level::Info, Location::caller(), message);
     }
     #[track_caller]
     pub fn warn(message: &str) {
-        let location = Location::caller();
-        warn!(
-            "{} {} {}",
-            Self::format_location(&location),
-            Self::format_level(Level::Warn),
-            message.yellow(),
-        );
+        Self::log(Level::Warn, Location::caller(), message);
     }
     #[track_caller]
     pub fn error(message: &str) {
+        Self::log(Level::Error, Location::caller(), message);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_json() {
         let location = Location::caller();
-        error!(
-            "{} {} {}",
-            Self::format_location(&location),
-            Self::format_level(Level::Error),
-            message.red(),
-        );
+        let line = Logger::format_json(Level::Warn, location, "Skipped \"a\"\nand b");
+        let value = serde_json::from_str::<serde_json::Value>(&line).unwrap();
+        assert!(!line.contains('\n'));
+        assert_eq!(value["level"], "warn");
+        assert_eq!(value["message"], "Skipped \"a\"\nand b");
+        assert_eq!(value["file"], file!());
+        assert_eq!(value["line"], location.line());
+        assert!(value["timestamp"].as_str().unwrap().ends_with('Z'));
     }
 }
@@ -1,94 +1,753 @@
-use clap::Parser;
-use globset::{Glob, GlobSetBuilder};
-
-pub mod args;
-pub mod codebase;
-pub mod error;
-pub mod gitignore;
-pub mod logger;
-pub mod os;
-pub mod tree;
-pub mod utils;
-
-use codebase::CodebaseBuilder;
-use error::{CunwError, Result};
+use std::{
+    collections::HashSet,
+    io::{IsTerminal, Write},
+    path::{Path, PathBuf},
+};
+
+use clap::{CommandFactory, FromArgMatches};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use tokio_util::sync::CancellationToken;
+
+use cunw::{archive, args, codebase, config, error, logger, presets, utils, watch};
+
+use archive::{ArchiveKind, ExtractedArchive};
+use args::Args;
+use codebase::{
+    format::OutputFormat, stats::ExclusionReason, Codebase, CodebaseBuilder, Precedence,
+};
+use config::Config;
+use error::{CunwError, CunwErrorKind, Result};
 use logger::Logger;
+use watch::ChangeFilter;
 
 /// Git related globs to ignore, I don't see a reason
 /// why we should consider these files but if you want
 /// to include them you can use `--dangerously-allow-dot-git-traversal` flag.
-const GIT_RELATED_IGNORE_PATTERNS: [&str; 2] = ["**/.git", "./**/.git"];
-const BASE_PATH_EDGE_CASES: [&str; 2] = [".", "./"];
+const GIT_RELATED_IGNORE_PATTERNS: [&str; 1] = ["**/.git"];
+/// Globs of the directories of the common version control systems, ignored with
+/// `--exclude-vcs` instead of only [`GIT_RELATED_IGNORE_PATTERNS`]. Like them, they
+/// are not ignored with `--dangerously-allow-dot-git-traversal`.
+const VCS_IGNORE_PATTERNS: [&str; 7] = [
+    "**/.git",
+    "**/.hg",
+    "**/.svn",
+    "**/.bzr",
+    "**/_darcs",
+    "**/.jj",
+    "**/.pijul",
+];
+/// Output path meaning "write to stdout".
+const STDOUT_OUTPUT: &str = "-";
+/// Output path used when none is given, nor set by [`OUTPUT_ENV_VAR`].
+const DEFAULT_OUTPUT: &str = "output.txt";
+/// Environment variable holding the output path used when neither `--output` nor the
+/// config file set it.
+const OUTPUT_ENV_VAR: &str = "CUNW_OUTPUT";
+/// Environment variable holding colon-separated globs to exclude,
+/// on top of the ones given with `--exclude`.
+const EXCLUDE_ENV_VAR: &str = "CUNW_EXCLUDE";
+/// Environment variable disabling the colors of the tree when set to a non-empty
+/// value (see <https://no-color.org>).
+const NO_COLOR_ENV_VAR: &str = "NO_COLOR";
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    // Record the start time of the program
-    // This is used to calculate the total time taken by the program
-    let start = std::time::Instant::now();
+/// Reads a file of glob patterns, one per line, into globs.
+///
+/// Empty lines and lines starting with `#` are skipped.
+/// Invalid patterns are reported with their line number and skipped, as are the
+/// `!pattern` negations, which only exist in gitignores.
+fn read_exclude_file(exclude_from: &Path) -> Result<Vec<Glob>> {
+    let content = std::fs::read_to_string(exclude_from)
+        .map_err(|err| CunwError::new(err.into()).with_file(exclude_from.to_path_buf()))?;
 
-    // Parse the command line arguments
-    let args = args::Args::parse();
+    let mut globs = Vec::new();
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if line.starts_with('!') {
+            Logger::warn(
+                format!(
+                    "Ignoring negated pattern at {}:{}, negations are not supported in exclude files: {}",
+                    exclude_from.display(),
+                    i + 1,
+                    line
+                )
+                .as_str(),
+            );
+            continue;
+        }
+        match utils::parse_glob(&utils::normalize_glob(line)) {
+            Ok(glob) => globs.push(glob),
+            Err(err) => Logger::warn(
+                format!(
+                    "Ignoring invalid pattern at {}:{}: {}",
+                    exclude_from.display(),
+                    i + 1,
+                    err
+                )
+                .as_str(),
+            ),
+        }
+    }
+    Ok(globs)
+}
 
-    // Set the log level based on the verbosity flag
-    logger::Logger::init(Some(args.verbosity.log_level_filter()));
+/// Returns the path of the output: `--output` (or the config file), then
+/// [`OUTPUT_ENV_VAR`], then a name based on the scanned directory with
+/// `--name-output-after-dir`, then [`DEFAULT_OUTPUT`].
+fn output_path(args: &Args) -> PathBuf {
+    if let Some(output) = &args.output {
+        return output.clone();
+    }
+    if let Some(output) = std::env::var_os(OUTPUT_ENV_VAR).filter(|value| !value.is_empty()) {
+        return PathBuf::from(output);
+    }
+    if args.name_output_after_dir {
+        if let Some(output) = args
+            .path
+            .canonicalize()
+            .ok()
+            .and_then(|root| utils::output_name_for_dir(&root))
+        {
+            return output;
+        }
+    }
+    PathBuf::from(DEFAULT_OUTPUT)
+}
 
-    // Build the excluded paths
+/// Returns the path of the `index`-th part of the output,
+/// e.g. `output.part1.txt` for `output.txt`.
+fn part_path(output: &Path, index: usize) -> PathBuf {
+    let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+    let file_name = match output.extension() {
+        Some(extension) => format!("{}.part{}.{}", stem, index, extension.to_string_lossy()),
+        None => format!("{}.part{}", stem, index),
+    };
+    output.with_file_name(file_name)
+}
+
+/// Returns the path of the file holding the SHA-256 of `output`,
+/// e.g. `output.txt.sha256` for `output.txt`.
+fn hash_path(output: &Path) -> PathBuf {
+    let mut file_name = output.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".sha256");
+    output.with_file_name(file_name)
+}
+
+/// Prints the SHA-256 of the written files to stderr (with `--print-hash`) and
+/// writes it next to them (with `--hash-file`), in the format of `sha256sum`.
+///
+/// The files are read back, so that the hashes are the ones of their exact bytes.
+fn write_hashes(args: &Args, written_files: &mut Vec<PathBuf>) -> Result<()> {
+    for file in written_files.clone() {
+        let content = std::fs::read(&file)
+            .map_err(|err| CunwError::new(err.into()).with_file(file.clone()))?;
+        let hash = utils::sha256_hex(&content);
+        if args.print_hash {
+            eprintln!("{}  {}", hash, file.display());
+        }
+        if args.hash_file {
+            let hash_file = hash_path(&file);
+            // Relative to the sidecar, so that `sha256sum -c` works from its directory
+            let line = format!(
+                "{}  {}\n",
+                hash,
+                file.file_name().unwrap_or_default().to_string_lossy()
+            );
+            std::fs::write(&hash_file, line)
+                .map_err(|err| CunwError::new(err.into()).with_file(hash_file.clone()))?;
+            written_files.push(hash_file);
+        }
+    }
+    Ok(())
+}
+
+/// Returns whether the tree written to stdout should be colored, which is only the
+/// case for a terminal and if the `NO_COLOR` environment variable is not set.
+fn use_colors(args: &Args) -> bool {
+    let to_stdout = output_path(args) == Path::new(STDOUT_OUTPUT);
+    let no_color = std::env::var_os(NO_COLOR_ENV_VAR).is_some_and(|value| !value.is_empty());
+    to_stdout && args.format == OutputFormat::Text && !no_color && std::io::stdout().is_terminal()
+}
+
+/// Fails with [`CunwErrorKind::OutputExists`] if `path` already exists.
+fn ensure_does_not_exist(path: &Path) -> Result<()> {
+    if path.exists() {
+        return Err(
+            CunwError::new(CunwErrorKind::OutputExists(path.to_path_buf()))
+                .with_file(path.to_path_buf()),
+        );
+    }
+    Ok(())
+}
+
+/// Returns the canonicalized output path if it is inside the scanned directory,
+/// where a later run would pick it up.
+fn output_inside_root(args: &Args) -> Option<PathBuf> {
+    let output = output_path(args);
+    if args.dry_run
+        || args.count_only
+        || args.output_dir.is_some()
+        || output == Path::new(STDOUT_OUTPUT)
+    {
+        return None;
+    }
+    let output = canonicalize_output(&output)?;
+    let root = args.path.canonicalize().ok()?;
+    output.starts_with(&root).then_some(output)
+}
+
+/// Returns the canonicalized `--output-dir` if it is inside the scanned directory.
+fn output_dir_inside_root(args: &Args) -> Option<PathBuf> {
+    if args.dry_run || args.count_only {
+        return None;
+    }
+    let output_dir = canonicalize_output(args.output_dir.as_ref()?)?;
+    let root = args.path.canonicalize().ok()?;
+    // Scanning the output directory itself would exclude everything
+    (output_dir.starts_with(&root) && output_dir != root).then_some(output_dir)
+}
+
+/// Canonicalizes an output path, which may not exist yet.
+fn canonicalize_output(output: &Path) -> Option<PathBuf> {
+    match output.canonicalize() {
+        Ok(output) => Some(output),
+        Err(_) => {
+            let parent = output
+                .parent()
+                .filter(|parent| !parent.as_os_str().is_empty())
+                .unwrap_or(Path::new("."));
+            Some(parent.canonicalize().ok()?.join(output.file_name()?))
+        }
+    }
+}
+
+/// Builds the glob set of the paths to exclude from the arguments.
+///
+/// **Returns**
+///
+/// The glob set, along with the patterns given by the user (`--exclude`,
+/// `--exclude-from` and `CUNW_EXCLUDE`) rather than the built-in ones.
+fn build_excluded_paths(args: &Args) -> Result<(GlobSet, Vec<Glob>)> {
     let mut excluded_paths = GlobSetBuilder::new();
-    if let Some(exclude) = args.exclude {
+    let mut user_patterns = Vec::new();
+    if let Some(output) = output_inside_root(args) {
+        Logger::warn(
+            format!(
+                "Excluding the output file {} from the scan",
+                output.display()
+            )
+            .as_str(),
+        );
+        let escaped = globset::escape(&output.to_string_lossy());
+        excluded_paths.add(Glob::new(&escaped).unwrap());
+        if args.hash_file {
+            excluded_paths.add(Glob::new(&format!("{}.sha256", escaped)).unwrap());
+        }
+        // Also exclude the parts written by --chunk-tokens
+        let parts = part_path(&output, 0).to_string_lossy().into_owned();
+        let (before, after) = parts.rsplit_once(".part0").unwrap();
+        let parts = format!(
+            "{}.part*{}",
+            globset::escape(before),
+            globset::escape(after)
+        );
+        excluded_paths.add(Glob::new(&parts).unwrap());
+        if args.hash_file {
+            excluded_paths.add(Glob::new(&format!("{}.sha256", parts)).unwrap());
+        }
+    }
+    if let Some(output_dir) = output_dir_inside_root(args) {
+        Logger::warn(
+            format!(
+                "Excluding the output directory {} from the scan",
+                output_dir.display()
+            )
+            .as_str(),
+        );
+        excluded_paths.add(Glob::new(&globset::escape(&output_dir.to_string_lossy())).unwrap());
+    }
+    if let Ok(exclude) = std::env::var(EXCLUDE_ENV_VAR) {
+        for pattern in exclude.split(':').filter(|pattern| !pattern.is_empty()) {
+            match utils::parse_glob(&utils::normalize_glob(pattern)) {
+                Ok(glob) => user_patterns.push(glob),
+                Err(err) => Logger::warn(
+                    format!("Ignoring invalid pattern in {}: {}", EXCLUDE_ENV_VAR, err).as_str(),
+                ),
+            }
+        }
+    }
+    if let Some(exclude) = &args.exclude {
         for glob in exclude {
-            // Edge case, if the path starts with '.' or './'
-            let excluded_path = {
-                let original_glob = glob.glob();
-                if let Some(path_prefix) =
-                    utils::start_with_one_of(&args.path.to_str().unwrap(), &BASE_PATH_EDGE_CASES)
-                {
-                    if let Some(glob_prefix) =
-                        utils::start_with_one_of(&original_glob, &BASE_PATH_EDGE_CASES)
-                    {
-                        original_glob.replacen(glob_prefix, path_prefix, 1)
-                    } else {
-                        format!("./{}", original_glob)
-                    }
-                } else {
-                    original_glob.to_string()
-                }
-            };
-            let glob = Glob::new(&excluded_path).unwrap();
-            excluded_paths.add(glob);
-        }
-    }
-    if !args.do_not_consider_ignore_files {
-        for pattern in GIT_RELATED_IGNORE_PATTERNS.iter() {
+            let excluded_path = utils::normalize_glob(glob.glob());
+            user_patterns.push(utils::parse_glob(&excluded_path).unwrap());
+        }
+    }
+    if let Some(exclude_from) = &args.exclude_from {
+        user_patterns.extend(read_exclude_file(exclude_from)?);
+    }
+    for glob in &user_patterns {
+        excluded_paths.add(glob.clone());
+    }
+    if let Some(presets) = &args.preset {
+        for preset in presets {
+            for pattern in preset.patterns() {
+                excluded_paths.add(Glob::new(pattern).unwrap());
+            }
+        }
+    }
+    if args.skip_generated {
+        for pattern in presets::GENERATED_PATTERNS.iter() {
+            excluded_paths.add(Glob::new(pattern).unwrap());
+        }
+    }
+    if !args.do_not_consider_ignore_files && !args.dangerously_allow_dot_git_traversal {
+        let vcs_patterns = if args.exclude_vcs {
+            &VCS_IGNORE_PATTERNS[..]
+        } else {
+            &GIT_RELATED_IGNORE_PATTERNS[..]
+        };
+        for pattern in vcs_patterns {
             excluded_paths.add(Glob::new(pattern).unwrap());
         }
     }
-    let excluded_paths = excluded_paths.build().unwrap();
+    Ok((excluded_paths.build().unwrap(), user_patterns))
+}
+
+/// Builds the glob set of patterns that only apply to one kind of entry, such as
+/// the ones of `--exclude-dir`.
+fn build_glob_set(globs: &Option<Vec<Glob>>) -> GlobSet {
+    let mut glob_set = GlobSetBuilder::new();
+    for glob in globs.iter().flatten() {
+        glob_set.add(utils::parse_glob(&utils::normalize_glob(glob.glob())).unwrap());
+    }
+    glob_set.build().unwrap()
+}
 
+/// Returns whether an entry of an archive, given by its path relative to the root and
+/// whether it is a directory, is excluded by the patterns, so that its content is
+/// not extracted.
+fn archive_exclusion<'a>(
+    args: &Args,
+    excluded_paths: &'a GlobSet,
+) -> impl Fn(&Path, bool) -> bool + 'a {
+    let excluded_dirs = build_glob_set(&args.exclude_dir);
+    let excluded_files = build_glob_set(&args.exclude_file);
+    // Like in the walk, the included names win over the patterns
+    let included_names = args
+        .include_name
+        .as_deref()
+        .filter(|_| args.precedence == Precedence::Include)
+        .map_or_else(HashSet::new, included_names);
+    move |relative_path, is_dir| {
+        let is_included_name = !is_dir
+            && relative_path
+                .file_name()
+                .is_some_and(|name| included_names.contains(name.to_string_lossy().as_ref()));
+        let excluded_by_kind = if is_dir {
+            &excluded_dirs
+        } else {
+            &excluded_files
+        };
+        !is_included_name
+            && (excluded_paths.is_match(relative_path) || excluded_by_kind.is_match(relative_path))
+    }
+}
+
+/// Returns the names of `--include-name`, as a set.
+fn included_names(include_name: &[String]) -> HashSet<String> {
+    include_name.iter().cloned().collect()
+}
+
+/// Warns about the exclude patterns given by the user that did not exclude anything,
+/// `user_patterns` being the ones returned by [`build_excluded_paths`].
+fn warn_unused_patterns(codebase: &Codebase, user_patterns: &[Glob], args: &Args) {
+    let normalized = |globs: &Option<Vec<Glob>>| {
+        globs
+            .iter()
+            .flatten()
+            .map(|glob| utils::parse_glob(&utils::normalize_glob(glob.glob())).unwrap())
+            .collect::<Vec<_>>()
+    };
+    let patterns = user_patterns
+        .iter()
+        .cloned()
+        .map(|glob| (glob, ExclusionReason::Pattern))
+        .chain(
+            normalized(&args.exclude_dir)
+                .into_iter()
+                .map(|glob| (glob, ExclusionReason::DirectoryPattern)),
+        )
+        .chain(
+            normalized(&args.exclude_file)
+                .into_iter()
+                .map(|glob| (glob, ExclusionReason::FilePattern)),
+        );
+    let root = codebase.root();
+    for (glob, kind) in patterns {
+        if !codebase.stats().is_pattern_used(root, &glob, &kind) {
+            Logger::warn(
+                format!(
+                    "The {} '{}' did not exclude anything, is it misspelled?",
+                    kind,
+                    glob.glob()
+                )
+                .as_str(),
+            );
+        }
+    }
+}
+
+/// Builds the codebase and writes the output.
+///
+/// If `cancellation_token` is cancelled during the build, the files found so far
+/// are written along with a trailing interrupted marker.
+///
+/// Unless `clobber` is set, existing output files are not overwritten and an error
+/// is returned instead.
+///
+/// **Returns**
+///
+/// The files that were written.
+async fn generate(
+    args: &Args,
+    excluded_paths: GlobSet,
+    user_patterns: &[Glob],
+    cancellation_token: Option<CancellationToken>,
+    clobber: bool,
+) -> Result<Vec<PathBuf>> {
     // Build Codebase
-    let codebase = CodebaseBuilder::new()
+    let mut codebase_builder = CodebaseBuilder::new();
+    if let Some(ext) = &args.ext {
+        // Accept both 'rs' and '.rs'
+        let extensions = ext
+            .iter()
+            .map(|extension| extension.trim().trim_start_matches('.').to_string())
+            .collect::<HashSet<_>>();
+        codebase_builder = codebase_builder.extensions(extensions);
+    }
+    if let Some(include_name) = &args.include_name {
+        codebase_builder = codebase_builder
+            .included_names(included_names(include_name))
+            .precedence(args.precedence);
+    }
+    if let Some(max_total_tokens) = args.max_total_tokens {
+        codebase_builder = codebase_builder.max_total_tokens(max_total_tokens);
+    }
+    if let Some(inline_under) = args.inline_under {
+        codebase_builder = codebase_builder.inline_under(inline_under);
+    }
+    if let Some(priority_ext) = &args.priority_ext {
+        let priority_extensions = priority_ext
+            .iter()
+            .map(|extension| extension.trim().trim_start_matches('.').to_string())
+            .collect();
+        codebase_builder = codebase_builder.priority_extensions(priority_extensions);
+    }
+    if let Some(root_label) = &args.root_label {
+        codebase_builder = codebase_builder.root_label(root_label.clone());
+    }
+    if let Some(print_path_style) = args.print_path_style {
+        codebase_builder = codebase_builder.path_style(print_path_style);
+    }
+    if let Some(file_separator) = &args.file_separator {
+        codebase_builder = codebase_builder.file_separator(file_separator.clone());
+    }
+    if let Some(file_template) = &args.file_template {
+        codebase_builder = codebase_builder.file_template(file_template.clone());
+    }
+    if let Some(tree_template) = &args.tree_template {
+        codebase_builder = codebase_builder.tree_template(tree_template.clone());
+    }
+    if let Some(relative_to) = &args.relative_to {
+        codebase_builder = codebase_builder.relative_to(relative_to.clone());
+    }
+    if let Some(stable_sort) = args.stable_sort {
+        codebase_builder = codebase_builder.stable_sort(stable_sort);
+    }
+    if let Some(max_file_size) = args.max_file_size {
+        codebase_builder = codebase_builder.max_file_size(max_file_size);
+    }
+    if let Some(min_file_size) = args.min_file_size {
+        codebase_builder = codebase_builder.min_file_size(min_file_size);
+    }
+    if let Some(modified_within) = args.modified_within {
+        codebase_builder = codebase_builder.modified_within(modified_within);
+    }
+    if let Some(max_files_per_dir) = args.max_files_per_dir {
+        codebase_builder = codebase_builder.max_files_per_dir(max_files_per_dir);
+    }
+    if let Some(after) = &args.after {
+        codebase_builder = codebase_builder.after(after.clone());
+    }
+    if let Some(max_symlink_depth) = args.max_symlink_depth {
+        codebase_builder = codebase_builder.max_symlink_depth(max_symlink_depth);
+    }
+    if let Some(max_line_length) = args.max_line_length {
+        codebase_builder = codebase_builder.max_line_length(max_line_length);
+    }
+    if let Some(tabs_to_spaces) = args.tabs_to_spaces {
+        codebase_builder = codebase_builder.tabs_to_spaces(tabs_to_spaces);
+    }
+    if let Some(concurrency) = args.concurrency {
+        codebase_builder = codebase_builder.concurrency(concurrency);
+    }
+    #[cfg(feature = "signatures")]
+    if args.signatures_only {
+        codebase_builder = codebase_builder.signatures_only(args.signatures_fallback);
+    }
+    if let Some(cancellation_token) = cancellation_token {
+        codebase_builder = codebase_builder.cancellation_token(cancellation_token);
+    }
+    let codebase = codebase_builder
         .excluded_paths(excluded_paths)
+        .excluded_dirs(build_glob_set(&args.exclude_dir))
+        .excluded_files(build_glob_set(&args.exclude_file))
+        .exit_on_non_utf8(args.exit_on_non_utf8)
+        .binary_mode(args.binary)
+        .text_encoding(args.encoding)
+        .mmap(args.mmap)
         .consider_gitignores(!args.do_not_consider_ignore_files)
-        .max_depth(args.max_depth.unwrap_or(std::usize::MAX))
+        .skip_root_gitignore(args.skip_root_gitignore)
+        .max_depth(args.max_depth.unwrap_or(usize::MAX))
         .follow_symlinks(args.follow_symbolic_links)
-        .build(args.path)
+        .absolute_paths(args.absolute_paths)
+        .dry_run(args.dry_run || args.count_only)
+        .strict_walk(args.strict_walk)
+        .strip_comments(args.strip_comments)
+        .show_mtime(args.show_mtime)
+        .tracked_only(args.tracked_only)
+        .normalize_newlines(args.normalize_newlines)
+        .header(args.header)
+        .git_info(args.git_info)
+        .progress(!args.quiet && !args.no_progress)
+        .toc(args.toc)
+        .json_pretty(args.json_pretty)
+        .tree_order(args.tree_order)
+        .flat(args.flat)
+        .include_empty_files(args.include_empty_files)
+        .path_attribute(!args.no_path_attr)
+        .flag_secrets(args.flag_secrets)
+        .skip_minified(args.skip_minified)
+        .color_tree(use_colors(args))
+        .build(args.path.clone())
         .await?;
+    let codebase = match &args.focus {
+        Some(focus) => codebase.focus(focus)?,
+        None => codebase,
+    };
+    codebase.stats().log_summary();
+    if args.warn_unused_patterns {
+        warn_unused_patterns(&codebase, user_patterns, args);
+    }
+    if args.list_excluded {
+        // To stderr, so that it does not mix with the output piped from stdout
+        eprint!("{}", codebase.stats().excluded_listing());
+    }
 
-    // Create and write to output file
-    let output_str = codebase.try_to_string()?;
-
-    let output = args
-        .output
-        .unwrap_or(std::path::PathBuf::from("output.txt"));
-    std::fs::write(output.clone(), output_str)
-        .map_err(|err| CunwError::new(err.into()).with_file(output))?;
-
-    // Record the end time of the program
-    let end = std::time::Instant::now();
-    // Calculate the time taken by the program
-    let time_taken = end - start;
-    let time_taken = time_taken.as_secs_f64();
-    // Print the time taken by the program
-    Logger::info(format!("Done in: {:.4} seconds\r\n", time_taken).as_str());
+    let mut written_files = Vec::new();
+    if args.count_only {
+        writeln!(std::io::stdout(), "{}", codebase.count_summary())
+            .map_err(|err| CunwError::new(err.into()))?;
+    } else if args.dry_run {
+        // Only list the files that would be included
+        let mut stdout = std::io::stdout();
+        for path in codebase.leaf_paths() {
+            writeln!(stdout, "{}", path.display())
+                .map_err(|err| CunwError::new(err.into()).with_file(path))?;
+        }
+    } else if let Some(output_dir) = &args.output_dir {
+        written_files = codebase.write_to_dir(output_dir, clobber)?;
+        Logger::info(
+            format!(
+                "Wrote {} files to {}",
+                written_files.len(),
+                output_dir.display()
+            )
+            .as_str(),
+        );
+    } else if let Some(chunk_tokens) = args.chunk_tokens {
+        let output = output_path(args);
+        if args.format != OutputFormat::Text || output == Path::new(STDOUT_OUTPUT) {
+            return Err(CunwError::new(CunwErrorKind::InvalidArgument(
+                "--chunk-tokens is only supported with the text format and an output file"
+                    .to_string(),
+            )));
+        }
+
+        // Create and write to output files, one per chunk
+        let chunks = codebase.try_to_chunks(chunk_tokens)?;
+        if !clobber {
+            for i in 0..chunks.len() {
+                ensure_does_not_exist(&part_path(&output, i + 1))?;
+            }
+        }
+        for (i, chunk) in chunks.iter().enumerate() {
+            let part = part_path(&output, i + 1);
+            std::fs::write(&part, chunk)
+                .map_err(|err| CunwError::new(err.into()).with_file(part.clone()))?;
+            written_files.push(part);
+        }
+        Logger::info(format!("Output split into {} parts", chunks.len()).as_str());
+    } else {
+        // Create and write to output file
+        let output = output_path(args);
+        if output == Path::new(STDOUT_OUTPUT) {
+            if args.hash_file {
+                return Err(CunwError::new(CunwErrorKind::InvalidArgument(
+                    "--hash-file is only supported with an output file".to_string(),
+                )));
+            }
+            // Logs go to stderr, so stdout only contains the output
+            let content = codebase.try_to_string_as(args.format)?;
+            std::io::stdout()
+                .write_all(content.as_bytes())
+                .map_err(|err| CunwError::new(err.into()).with_file(output))?;
+            if args.print_hash {
+                eprintln!("{}  -", utils::sha256_hex(content.as_bytes()));
+            }
+        } else {
+            if !clobber {
+                ensure_does_not_exist(&output)?;
+            }
+            codebase.write_to_path(&output, args.format)?;
+            written_files.push(output);
+        }
+    }
+    if args.print_hash || args.hash_file {
+        write_hashes(args, &mut written_files)?;
+    }
+    Ok(written_files)
+}
+
+fn main() -> Result<()> {
+    // Record the start time of the program
+    // This is used to calculate the total time taken by the program
+    let start = std::time::Instant::now();
+
+    // Parse the command line arguments
+    let matches = Args::command().get_matches();
+    let mut args = Args::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
+
+    // Set the log level based on the verbosity flag
+    let log_file = args
+        .log_file
+        .as_ref()
+        .map(|log_file| {
+            std::fs::File::create(log_file)
+                .map_err(|err| CunwError::new(err.into()).with_file(log_file.clone()))
+        })
+        .transpose()?;
+    logger::Logger::set_format(args.log_format);
+    logger::Logger::init_with_log_file(Some(args.log_level_filter()), log_file);
+
+    // Fill the arguments that were not given from the config file
+    if let Some(config) = Config::discover(&args)? {
+        config.apply(&mut args, &matches)?;
+    }
+    if args.print_config {
+        print!("{}", Config::from_args(&args).to_toml());
+        return Ok(());
+    }
+
+    // The same knob bounds the worker threads and the files read at once
+    let concurrency = *args
+        .concurrency
+        .get_or_insert_with(utils::default_concurrency);
+    tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(concurrency)
+        .enable_all()
+        .build()
+        .map_err(|err| CunwError::new(err.into()))?
+        .block_on(run(args, start))
+}
+
+/// Runs cunw with the arguments completed from the config file.
+async fn run(mut args: Args, start: std::time::Instant) -> Result<()> {
+    // Build the excluded paths
+    let (excluded_paths, user_patterns) = build_excluded_paths(&args)?;
+
+    // An archive is extracted and walked like a directory, which lives until the end
+    let _extracted = match ArchiveKind::from_path(&args.path).filter(|_| args.path.is_file()) {
+        Some(_) if args.watch => {
+            return Err(CunwError::new(CunwErrorKind::InvalidArgument(
+                "--watch is not supported when scanning an archive".to_string(),
+            )));
+        }
+        Some(kind) => {
+            let extracted = ExtractedArchive::extract(
+                &args.path,
+                kind,
+                args.max_archive_size
+                    .unwrap_or(archive::DEFAULT_MAX_EXTRACTED_SIZE),
+                archive_exclusion(&args, &excluded_paths),
+            )?;
+            args.path = extracted.root().to_path_buf();
+            Some(extracted)
+        }
+        None => None,
+    };
+
+    if args.watch {
+        let filter = ChangeFilter::new(
+            watch::watched_root(&args.path)?,
+            excluded_paths.clone(),
+            build_glob_set(&args.exclude_dir),
+            build_glob_set(&args.exclude_file),
+            !args.do_not_consider_ignore_files,
+        )
+        .included_names(
+            args.include_name
+                .as_deref()
+                .filter(|_| args.precedence == Precedence::Include)
+                .map_or_else(HashSet::new, included_names),
+        )
+        .skip_root_gitignore(args.skip_root_gitignore);
+        // Only the first build may refuse to overwrite, the next ones replace our own output
+        let mut clobber = args.clobber();
+        return watch::watch(filter, || {
+            let build = generate(&args, excluded_paths.clone(), &user_patterns, None, clobber);
+            clobber = true;
+            build
+        })
+        .await;
+    }
+
+    // On Ctrl-C, stop walking and write what has been collected so far
+    let cancellation_token = CancellationToken::new();
+    tokio::spawn({
+        let cancellation_token = cancellation_token.clone();
+        async move {
+            if tokio::signal::ctrl_c().await.is_ok() {
+                Logger::warn(
+                    "Interrupted, writing the partial output (press Ctrl-C again to exit now)",
+                );
+                cancellation_token.cancel();
+            }
+            if tokio::signal::ctrl_c().await.is_ok() {
+                std::process::exit(130);
+            }
+        }
+    });
+    generate(
+        &args,
+        excluded_paths,
+        &user_patterns,
+        Some(cancellation_token),
+        args.clobber(),
+    )
+    .await?;
+
+    if !args.quiet {
+        // Record the end time of the program
+        let end = std::time::Instant::now();
+        // Calculate the time taken by the program
+        let time_taken = end - start;
+        let time_taken = time_taken.as_secs_f64();
+        // Print the time taken by the program
+        Logger::info(format!("Done in: {:.4} seconds\r\n", time_taken).as_str());
+    }
 
     Ok(())
 }
@@ -0,0 +1,109 @@
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+/// Globs excluded by the `node` preset.
+pub const NODE_PATTERNS: [&str; 6] = [
+    "**/node_modules",
+    "**/bower_components",
+    "**/.next",
+    "**/.nuxt",
+    "**/.parcel-cache",
+    "**/.turbo",
+];
+
+/// Globs excluded by the `rust` preset.
+pub const RUST_PATTERNS: [&str; 1] = ["**/target"];
+
+/// Globs excluded by the `python` preset.
+pub const PYTHON_PATTERNS: [&str; 9] = [
+    "**/__pycache__",
+    "**/*.pyc",
+    "**/*.pyo",
+    "**/.venv",
+    "**/venv",
+    "**/.pytest_cache",
+    "**/.mypy_cache",
+    "**/.tox",
+    "**/*.egg-info",
+];
+
+/// Globs of the generated and vendored files excluded by `--skip-generated`,
+/// whatever the presets.
+pub const GENERATED_PATTERNS: [&str; 8] = [
+    "**/*.min.js",
+    "**/*.min.css",
+    "**/*.map",
+    "**/package-lock.json",
+    "**/yarn.lock",
+    "**/pnpm-lock.yaml",
+    "**/Cargo.lock",
+    "**/*.snap",
+];
+
+/// Sets of exclude globs for common ecosystems.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Preset {
+    Node,
+    Rust,
+    Python,
+}
+
+impl Preset {
+    /// Returns the globs this preset adds to the excluded paths.
+    pub fn patterns(&self) -> &'static [&'static str] {
+        match self {
+            Preset::Node => &NODE_PATTERNS,
+            Preset::Rust => &RUST_PATTERNS,
+            Preset::Python => &PYTHON_PATTERNS,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use globset::Glob;
+
+    #[test]
+    fn test_presets_patterns_are_valid_globs() {
+        let presets_patterns = Preset::value_variants()
+            .iter()
+            .flat_map(|preset| preset.patterns());
+        for pattern in presets_patterns.chain(&GENERATED_PATTERNS) {
+            assert!(Glob::new(pattern).is_ok(), "Invalid glob: {}", pattern);
+        }
+    }
+
+    #[test]
+    fn test_presets_match_common_paths() {
+        let matches = |preset: Preset, path: &str| {
+            preset
+                .patterns()
+                .iter()
+                .any(|pattern| Glob::new(pattern).unwrap().compile_matcher().is_match(path))
+        };
+        assert!(matches(Preset::Node, "node_modules"));
+        assert!(matches(Preset::Node, "packages/app/node_modules"));
+        assert!(matches(Preset::Rust, "target"));
+        assert!(matches(Preset::Python, "src/__pycache__"));
+        assert!(matches(Preset::Python, "src/module.pyc"));
+        assert!(!matches(Preset::Rust, "src/main.rs"));
+    }
+
+    #[test]
+    fn test_generated_patterns_match_common_paths() {
+        let matches = |path: &str| {
+            GENERATED_PATTERNS
+                .iter()
+                .any(|pattern| Glob::new(pattern).unwrap().compile_matcher().is_match(path))
+        };
+        assert!(matches("dist/app.min.js"));
+        assert!(matches("dist/app.js.map"));
+        assert!(matches("package-lock.json"));
+        assert!(matches("crates/core/Cargo.lock"));
+        assert!(matches("src/__snapshots__/app.test.js.snap"));
+        assert!(!matches("src/app.js"));
+        assert!(!matches("Cargo.toml"));
+    }
+}
@@ -0,0 +1,180 @@
+use clap::ValueEnum;
+use tree_sitter::{Node, Parser};
+
+use crate::language::Language;
+
+const INDENT: &str = "    ";
+
+/// What to do with the files of unrecognized languages when only emitting signatures.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum SignaturesFallback {
+    /// Emit the full content of the file.
+    #[default]
+    Full,
+    /// Do not emit the content of the file.
+    Skip,
+}
+
+/// Extracts the declarations (functions, structs, classes, ...) of a source file,
+/// without their bodies.
+///
+/// # Arguments
+///
+/// * `language` - The [`Language`] of the source file.
+/// * `source` - The content of the source file.
+///
+/// # Returns
+///
+/// * `Some(signatures)` with one declaration per line (nested declarations being indented).
+/// * `None` if the language is not supported or the source could not be parsed.
+pub fn extract_signatures(language: Language, source: &str) -> Option<String> {
+    type PushSignatures = fn(Node, &str, usize, &mut Vec<String>);
+    let (grammar, push_signatures): (tree_sitter::Language, PushSignatures) = match language {
+        Language::Rust => (tree_sitter_rust::LANGUAGE.into(), push_rust_signatures),
+        Language::Python => (tree_sitter_python::LANGUAGE.into(), push_python_signatures),
+        _ => return None,
+    };
+    let mut parser = Parser::new();
+    parser.set_language(&grammar).ok()?;
+    let tree = parser.parse(source, None)?;
+
+    let mut signatures = Vec::new();
+    push_signatures(tree.root_node(), source, 0, &mut signatures);
+    Some(signatures.join("\n"))
+}
+
+/// Returns the source of `node` up to its body, if it has one.
+fn header<'a>(node: Node, source: &'a str) -> &'a str {
+    let end = node
+        .child_by_field_name("body")
+        .map_or(node.end_byte(), |body| body.start_byte());
+    source[node.start_byte()..end].trim_end()
+}
+
+fn push_rust_signatures(node: Node, source: &str, depth: usize, signatures: &mut Vec<String>) {
+    let indent = INDENT.repeat(depth);
+    let mut cursor = node.walk();
+    for child in node.named_children(&mut cursor) {
+        match child.kind() {
+            "function_item" => signatures.push(format!("{}{};", indent, header(child, source))),
+            "impl_item" | "trait_item" | "mod_item" => match child.child_by_field_name("body") {
+                Some(body) => {
+                    signatures.push(format!("{}{} {{", indent, header(child, source)));
+                    push_rust_signatures(body, source, depth + 1, signatures);
+                    signatures.push(format!("{}}}", indent));
+                }
+                None => signatures.push(format!("{}{}", indent, &source[child.byte_range()])),
+            },
+            "attribute_item"
+            | "function_signature_item"
+            | "struct_item"
+            | "enum_item"
+            | "union_item"
+            | "type_item"
+            | "const_item"
+            | "static_item"
+            | "associated_type" => {
+                signatures.push(format!("{}{}", indent, &source[child.byte_range()]))
+            }
+            _ => {}
+        }
+    }
+}
+
+fn push_python_signatures(node: Node, source: &str, depth: usize, signatures: &mut Vec<String>) {
+    let indent = INDENT.repeat(depth);
+    let mut cursor = node.walk();
+    for child in node.named_children(&mut cursor) {
+        let definition = match child.kind() {
+            "decorated_definition" => {
+                let mut decorators_cursor = child.walk();
+                for decorator in child.named_children(&mut decorators_cursor) {
+                    if decorator.kind() == "decorator" {
+                        signatures.push(format!("{}{}", indent, &source[decorator.byte_range()]));
+                    }
+                }
+                match child.child_by_field_name("definition") {
+                    Some(definition) => definition,
+                    None => continue,
+                }
+            }
+            _ => child,
+        };
+        match definition.kind() {
+            "function_definition" => {
+                signatures.push(format!("{}{} ...", indent, header(definition, source)))
+            }
+            "class_definition" => {
+                signatures.push(format!("{}{}", indent, header(definition, source)));
+                let before = signatures.len();
+                if let Some(body) = definition.child_by_field_name("body") {
+                    push_python_signatures(body, source, depth + 1, signatures);
+                }
+                // Keep the class valid Python when it has no methods
+                if signatures.len() == before {
+                    signatures.last_mut().unwrap().push_str(" ...");
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rust_signatures() {
+        let source = r#"
+use std::fmt;
+
+/// A point.
+#[derive(Debug)]
+pub struct Point {
+    x: i32,
+}
+
+impl Point {
+    pub fn new(x: i32) -> Self {
+        Self { x }
+    }
+}
+
+fn main() {
+    println!("{:?}", Point::new(1));
+}
+"#;
+        let signatures = extract_signatures(Language::Rust, source).unwrap();
+        assert_eq!(
+            signatures,
+            "#[derive(Debug)]\npub struct Point {\n    x: i32,\n}\nimpl Point {\n    pub fn new(x: i32) -> Self;\n}\nfn main();"
+        );
+    }
+
+    #[test]
+    fn test_python_signatures() {
+        let source = r#"
+import os
+
+class Greeter(Base):
+    def __init__(self, name):
+        self.name = name
+
+    @staticmethod
+    def greet(name: str) -> str:
+        return f"Hello {name}"
+
+class Empty:
+    pass
+
+def main():
+    print(Greeter("world"))
+"#;
+        let signatures = extract_signatures(Language::Python, source).unwrap();
+        assert_eq!(
+            signatures,
+            "class Greeter(Base):\n    def __init__(self, name): ...\n    @staticmethod\n    def greet(name: str) -> str: ...\nclass Empty: ...\ndef main(): ..."
+        );
+    }
+}
@@ -1,15 +1,96 @@
 use std::{
+    cmp::Ordering,
+    ffi::OsStr,
     fmt::Display,
     path::{Path, PathBuf},
-    sync::{Arc, Mutex, OnceLock, Weak},
+    sync::{
+        atomic::{AtomicUsize, Ordering as AtomicOrdering},
+        Arc, Mutex, OnceLock, PoisonError, Weak,
+    },
 };
 
-use crate::{gitignore::GitIgnore, logger::Logger};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use unicode_normalization::UnicodeNormalization;
+
+use crate::{gitignore::GitIgnore, logger::Logger, utils};
 
 const IS_CHILD_GLIPH: &str = "├─ ";
 const LAST_CHILD_GLIPH: &str = "└─ ";
 const SKIP_GLIPH: &str = "│  ";
 const SKIP_GLIPH_GAP: &str = "   ";
+/// ANSI escape codes used to render the directories in bold blue.
+const DIRECTORY_COLOR: &str = "\x1b[1;34m";
+const RESET_COLOR: &str = "\x1b[0m";
+
+/// The order of the directories and files at each level of the tree.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TreeOrder {
+    /// The directories, then the files.
+    #[default]
+    DirsFirst,
+    /// The files, then the directories.
+    FilesFirst,
+    /// The directories and files sorted together by name.
+    Mixed,
+}
+
+/// How to sort the entries of each directory so that the output is the same on
+/// every platform, instead of by their raw (platform dependent) file names.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum StableSort {
+    /// By the Unicode NFC normalization of the UTF-8 names.
+    Nfc,
+    /// By the lowercased Unicode NFC normalization of the UTF-8 names.
+    NfcLowercase,
+}
+
+impl StableSort {
+    /// Returns the key by which `name` is sorted.
+    ///
+    /// Names that are not valid UTF-8 are converted lossily first.
+    pub fn key(&self, name: &OsStr) -> String {
+        let normalized = name.to_string_lossy().nfc().collect::<String>();
+        match self {
+            StableSort::Nfc => normalized,
+            StableSort::NfcLowercase => normalized.to_lowercase(),
+        }
+    }
+    /// Compares two names by their [`StableSort::key`], falling back to their raw
+    /// bytes for names with the same key (e.g. `README.md` and `readme.md` when
+    /// lowercased), so that the order is fully deterministic.
+    pub fn compare(&self, a: &OsStr, b: &OsStr) -> Ordering {
+        self.key(a)
+            .cmp(&self.key(b))
+            .then_with(|| a.as_encoded_bytes().cmp(b.as_encoded_bytes()))
+    }
+}
+
+/// A child of a tree node, either a branch (directory), a leaf (file), or the
+/// number of leaves that were left out of it.
+enum TreeChild<T: Clone + PartialEq + Display> {
+    Branch(Arc<Tree<T>>),
+    Leaf(T),
+    Omitted(usize),
+}
+
+impl<T: Clone + PartialEq + Display> TreeChild<T> {
+    /// Returns the name under which the child is rendered, without the `/` of
+    /// directories.
+    fn name(&self) -> String {
+        match self {
+            TreeChild::Branch(branch) => branch.label(),
+            TreeChild::Leaf(leaf) => leaf.to_string(),
+            TreeChild::Omitted(count) => format!(
+                "... ({} more {} omitted)",
+                count,
+                if *count == 1 { "file" } else { "files" }
+            ),
+        }
+    }
+}
 
 /// Represents a tree structure for storing hierarchical data.
 #[derive(Debug, Clone)]
@@ -28,6 +109,12 @@ pub struct Tree<T: Clone + PartialEq + Display> {
     gitignore: Arc<OnceLock<GitIgnore>>,
     /// The child branches (directories) of this tree node.
     branches: Arc<Mutex<Vec<Arc<Tree<T>>>>>,
+    /// The name under which this tree node is rendered, instead of the name of
+    /// its directory.
+    label: Arc<OnceLock<String>>,
+    /// The number of leaves (files) left out of this tree node, rendered as a
+    /// single line after its leaves.
+    omitted_leaves: Arc<AtomicUsize>,
 }
 
 impl<T: Clone + PartialEq + Display> Tree<T> {
@@ -49,6 +136,8 @@ impl<T: Clone + PartialEq + Display> Tree<T> {
             leaves: Arc::new(Mutex::new(Vec::new())),
             gitignore: Arc::new(OnceLock::new()),
             branches: Arc::new(Mutex::new(Vec::new())),
+            label: Arc::new(OnceLock::new()),
+            omitted_leaves: Arc::new(AtomicUsize::new(0)),
         })
     }
 
@@ -62,6 +151,25 @@ impl<T: Clone + PartialEq + Display> Tree<T> {
         leaves.push(leaf);
     }
 
+    /// Returns the number of leaves (files) of this tree node, without the ones of
+    /// its branches.
+    pub fn leaf_count(&self) -> usize {
+        self.leaves
+            .lock()
+            .expect("Failed to lock leaves mutex")
+            .len()
+    }
+
+    /// Records that a leaf (file) was left out of this tree node.
+    pub fn omit_leaf(&self) {
+        self.omitted_leaves.fetch_add(1, AtomicOrdering::Relaxed);
+    }
+
+    /// Returns the number of leaves (files) left out of this tree node.
+    pub fn omitted_leaves(&self) -> usize {
+        self.omitted_leaves.load(AtomicOrdering::Relaxed)
+    }
+
     /// Adds a branch (directory) to the tree.
     ///
     /// # Arguments
@@ -77,6 +185,35 @@ impl<T: Clone + PartialEq + Display> Tree<T> {
         &self.current_dir
     }
 
+    /// Returns the name under which this tree node is rendered: its label if set,
+    /// or else the name of its directory (empty for `/`).
+    pub fn label(&self) -> String {
+        match self.label.get() {
+            Some(label) => label.clone(),
+            None => self
+                .current_dir
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Sets the name under which this tree node is rendered.
+    ///
+    /// # Arguments
+    ///
+    /// * `label` - The name to render instead of the name of the directory.
+    pub fn set_label(&self, label: String) {
+        self.label.set(label).expect("Failed to set label");
+    }
+
+    /// Returns this tree node as an [`Arc`].
+    fn arc(&self) -> Arc<Tree<T>> {
+        self._weak_self
+            .upgrade()
+            .expect("A tree node is always held by an Arc")
+    }
+
     /// Returns the parent tree node, if any.
     pub fn parent(&self) -> Option<Arc<Tree<T>>> {
         self.parent.as_ref().and_then(|parent| parent.upgrade())
@@ -84,28 +221,46 @@ impl<T: Clone + PartialEq + Display> Tree<T> {
 
     /// Returns the first GitIgnore instance that applies to this tree node.
     pub fn gitignore(&self) -> Option<GitIgnore> {
-        if self.gitignore.get().is_some() {
-            Logger::trace(
-                format!(
-                    "GitIgnore found for current tree node: {}",
-                    self.current_dir.display()
-                )
-                .as_str(),
-            );
-            return self.gitignore.get().cloned();
-        }
-        if let Some(parent) = self.parent() {
+        // Iterative rather than recursive, so that deep trees do not overflow the stack
+        let mut tree = self.arc();
+        loop {
+            if let Some(gitignore) = tree.gitignore.get() {
+                Logger::trace(
+                    format!(
+                        "GitIgnore found for current tree node: {}",
+                        tree.current_dir.display()
+                    )
+                    .as_str(),
+                );
+                return Some(gitignore.clone());
+            }
+            let Some(parent) = tree.parent() else {
+                Logger::trace(format!("Exhausted all parent nodes to find GitIgnore, ending search at current tree node: {}", tree.current_dir.display()).as_str());
+                return None;
+            };
             Logger::trace(
                 format!(
                     "No GitIgnore found for current tree node: {}, checking parent",
-                    self.current_dir.display()
+                    tree.current_dir.display()
                 )
                 .as_str(),
             );
-            return parent.gitignore();
+            tree = parent;
         }
-        Logger::trace(format!("Exhausted all parent nodes to find GitIgnore, ending search at current tree node: {}", self.current_dir.display()).as_str());
-        None
+    }
+
+    /// Returns all the GitIgnore instances that apply to this tree node, from the
+    /// one of this tree node (or its nearest ancestor) up to the root.
+    pub fn gitignores(&self) -> Vec<GitIgnore> {
+        let mut gitignores = Vec::new();
+        let mut tree = Some(self.arc());
+        while let Some(current) = tree {
+            if let Some(gitignore) = current.gitignore.get() {
+                gitignores.push(gitignore.clone());
+            }
+            tree = current.parent();
+        }
+        gitignores
     }
 
     /// Sets the GitIgnore instance for this tree node.
@@ -129,11 +284,12 @@ impl<T: Clone + PartialEq + Display> Tree<T> {
     ///
     /// The Tree instance that contains the given path, or None if not found.
     pub fn backtrack_to_branch(&self, path: &Path) -> Option<Arc<Tree<T>>> {
-        if path == self.current_dir() {
-            return Some(self._weak_self.upgrade().unwrap());
-        }
-        if let Some(parent) = self.parent() {
-            return parent.backtrack_to_branch(path);
+        let mut tree = Some(self.arc());
+        while let Some(current) = tree {
+            if path == current.current_dir() {
+                return Some(current);
+            }
+            tree = current.parent();
         }
         None
     }
@@ -144,19 +300,15 @@ impl<T: Clone + PartialEq + Display> Tree<T> {
     ///
     /// A vector containing all leaves in the tree.
     pub fn collect_all_leaves(&self) -> Vec<T> {
-        let mut local_leaves = {
-            let local_leaves_lock = self.leaves.lock().unwrap();
-            local_leaves_lock
-                .iter()
-                .map(|leave| leave.clone())
-                .collect::<Vec<_>>()
-        };
-        let mut branches_leaves = Vec::new();
-        for branch in self.branches.lock().unwrap().iter() {
-            branches_leaves.extend(branch.collect_all_leaves());
+        let mut leaves = Vec::new();
+        // Depth first with an explicit stack, so that deep trees do not overflow the
+        // call stack; the branches are reversed so that the first one is popped first
+        let mut stack = vec![self.arc()];
+        while let Some(tree) = stack.pop() {
+            leaves.extend(tree.collect_local_leaves());
+            stack.extend(tree.collect_local_branches().into_iter().rev());
         }
-        local_leaves.extend(branches_leaves);
-        local_leaves
+        leaves
     }
 
     /// Collects all leaves (files) at this tree node.
@@ -177,87 +329,337 @@ impl<T: Clone + PartialEq + Display> Tree<T> {
         self.branches.lock().unwrap().clone()
     }
 
-    /// Generates a string representation of the tree structure.
+    /// Returns a copy of this tree with only the leaves (files) matching `predicate`.
     ///
-    /// # Returns
+    /// The branches (directories) left without any leaf are pruned, but the ones that
+    /// were already empty are kept, as is the label of the root.
     ///
-    /// A string representing the tree structure.
-    pub fn to_string(&self) -> String {
-        let mut buffer = String::new();
-        self.build_string(&mut buffer, "", true);
-        // Remove the last newline character
-        buffer.pop();
-        buffer
+    /// # Arguments
+    ///
+    /// * `predicate` - Whether a leaf is kept.
+    pub fn filter_leaves(&self, predicate: &impl Fn(&T) -> bool) -> Arc<Tree<T>> {
+        self.filtered(None, predicate)
+            .expect("The root of the filtered tree is never pruned")
     }
 
-    /// Helper method to recursively build the string representation of the tree.
+    /// Returns a copy of this tree node with only the leaves matching `predicate`, or
+    /// [`None`] if it is a branch whose leaves were all filtered out.
+    ///
+    /// The tree is walked with an explicit stack rather than recursively, so that deep
+    /// trees do not overflow the call stack.
+    fn filtered(
+        &self,
+        parent: Option<Weak<Tree<T>>>,
+        predicate: &impl Fn(&T) -> bool,
+    ) -> Option<Arc<Tree<T>>> {
+        // The tree nodes depth first, each with the index of its parent, so that a
+        // branch always comes after its parent
+        let mut nodes: Vec<(Arc<Tree<T>>, Option<usize>)> = Vec::new();
+        let mut stack = vec![(self.arc(), None)];
+        while let Some((tree, parent_index)) = stack.pop() {
+            let index = nodes.len();
+            stack.extend(
+                tree.collect_local_branches()
+                    .into_iter()
+                    .rev()
+                    .map(|branch| (branch, Some(index))),
+            );
+            nodes.push((tree, parent_index));
+        }
+
+        // From the deepest tree nodes up, whether each one is kept, i.e. whether it
+        // keeps a leaf or a branch, or had no leaf to begin with
+        let mut is_kept = vec![false; nodes.len()];
+        let mut was_empty = vec![true; nodes.len()];
+        for (index, (tree, parent_index)) in nodes.iter().enumerate().rev() {
+            let leaves = tree.leaves.lock().expect("Failed to lock leaves mutex");
+            was_empty[index] &= leaves.is_empty();
+            is_kept[index] |= was_empty[index] || leaves.iter().any(predicate);
+            if let Some(parent_index) = *parent_index {
+                was_empty[parent_index] &= was_empty[index];
+                is_kept[parent_index] |= is_kept[index];
+            }
+        }
+        // The root of the filtered tree is never pruned
+        is_kept[0] |= parent.is_none();
+
+        // The copies are made from the root down, since a tree node refers to its parent
+        let mut copies: Vec<Option<Arc<Tree<T>>>> = Vec::with_capacity(nodes.len());
+        for (index, (tree, parent_index)) in nodes.iter().enumerate() {
+            let copy = match parent_index {
+                None => is_kept[index].then(|| Tree::new(tree.current_dir.clone(), parent.clone())),
+                Some(parent_index) => copies[*parent_index]
+                    .as_ref()
+                    .filter(|_| is_kept[index])
+                    .map(|parent| {
+                        let copy =
+                            Tree::new(tree.current_dir.clone(), Some(Arc::downgrade(parent)));
+                        parent.add_branch(copy.clone());
+                        copy
+                    }),
+            };
+            if let Some(copy) = &copy {
+                if let Some(label) = tree.label.get() {
+                    copy.set_label(label.clone());
+                }
+                copy.omitted_leaves
+                    .store(tree.omitted_leaves(), AtomicOrdering::Relaxed);
+                for leaf in tree.collect_local_leaves().into_iter().filter(predicate) {
+                    copy.add_leaf(leaf);
+                }
+            }
+            copies.push(copy);
+        }
+        copies.swap_remove(0)
+    }
+
+    /// Returns a copy of this tree restricted to `path`: the branches (directories) at
+    /// or below it are kept whole, their ancestors only keep their leaves for which
+    /// `is_in_focus` is true (e.g. when `path` is a file), and the rest is pruned.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The absolute path of the directory or file to focus on.
+    /// * `is_in_focus` - Whether a leaf of an ancestor of `path` is kept.
+    pub fn focus(&self, path: &Path, is_in_focus: &impl Fn(&T) -> bool) -> Arc<Tree<T>> {
+        let mut root = None;
+        // Each tree node to copy, along with the copy of its parent; an explicit stack
+        // rather than recursion, so that deep trees do not overflow the call stack
+        let mut stack = vec![(self.arc(), None::<Arc<Tree<T>>>)];
+        while let Some((tree, parent)) = stack.pop() {
+            let parent_weak = parent.as_ref().map(Arc::downgrade);
+            let copy = if tree.current_dir.starts_with(path) {
+                tree.filtered(parent_weak, &|_| true)
+                    .expect("A tree without filtered out leaves is never pruned")
+            } else {
+                let copy = Tree::new(tree.current_dir.clone(), parent_weak);
+                if let Some(label) = tree.label.get() {
+                    copy.set_label(label.clone());
+                }
+                for leaf in tree.collect_local_leaves().into_iter().filter(is_in_focus) {
+                    copy.add_leaf(leaf);
+                }
+                // Reversed, so that the first branch is popped first
+                for branch in tree.collect_local_branches().into_iter().rev() {
+                    if path.starts_with(branch.current_dir()) {
+                        stack.push((branch, Some(copy.clone())));
+                    }
+                }
+                copy
+            };
+            match parent {
+                Some(parent) => parent.add_branch(copy),
+                None => root = Some(copy),
+            }
+        }
+        root.expect("The root is always copied")
+    }
+
+    /// Returns the branches (directories) and leaves (files) at this tree node,
+    /// in the given order.
+    fn children(&self, order: TreeOrder) -> Vec<TreeChild<T>> {
+        let branches = self
+            .collect_local_branches()
+            .into_iter()
+            .map(TreeChild::Branch);
+        // The omitted leaves follow the other leaves
+        let omitted = Some(self.omitted_leaves())
+            .filter(|count| *count > 0)
+            .map(TreeChild::Omitted);
+        let leaves = self.collect_local_leaves().into_iter().map(TreeChild::Leaf);
+        match order {
+            TreeOrder::DirsFirst => branches.chain(leaves).chain(omitted).collect(),
+            TreeOrder::FilesFirst => leaves.chain(omitted).chain(branches).collect(),
+            TreeOrder::Mixed => {
+                let mut children = branches.chain(leaves).collect::<Vec<_>>();
+                // Normalized so that the order does not depend on the platform, the raw
+                // names telling apart the ones that normalize the same
+                children.sort_by_cached_key(|child| {
+                    let name = child.name();
+                    (name.nfc().collect::<String>(), name)
+                });
+                children.extend(omitted);
+                children
+            }
+        }
+    }
+
+    /// Collects all leaves (files) from this tree node and its branches, in the
+    /// order they are rendered with `order`.
+    pub fn collect_leaves_in_order(&self, order: TreeOrder) -> Vec<T> {
+        let mut leaves = Vec::new();
+        let mut stack = self.children(order);
+        stack.reverse();
+        while let Some(child) = stack.pop() {
+            match child {
+                TreeChild::Branch(branch) => stack.extend(branch.children(order).into_iter().rev()),
+                TreeChild::Leaf(leaf) => leaves.push(leaf),
+                TreeChild::Omitted(_) => {}
+            }
+        }
+        leaves
+    }
+
+    /// Helper method to build the string representation of the tree, one line per
+    /// branch or leaf.
+    ///
+    /// The tree is walked depth first with an explicit stack rather than recursively,
+    /// so that deep trees do not overflow the call stack.
     ///
     /// # Arguments
     ///
     /// * `buffer` - The string buffer to append to.
-    /// * `prefix` - The prefix to use for the current line.
-    /// * `is_last` - Whether this is the last item in the current level.
-    fn build_string(&self, buffer: &mut String, branch_prefix: &str, is_last_at_level: bool) {
-        let branches_len = self.branches.lock().unwrap().len();
-        let leaves_len = self.leaves.lock().unwrap().len();
-        let dir_name = self.current_dir.file_name().map(|f| f.to_str().unwrap());
-
-        let current_branch_display = format!(
-            "{}{}/{}\n",
-            branch_prefix,
-            if (branches_len > 1 || !is_last_at_level) && dir_name.is_some() {
-                IS_CHILD_GLIPH
-            } else if dir_name.is_some() {
+    /// * `color` - Whether to color the directories.
+    /// * `order` - The order of the directories and files of each level.
+    /// * `leaf_display` - Renders a leaf on its line.
+    fn build_string(
+        &self,
+        buffer: &mut String,
+        color: bool,
+        order: TreeOrder,
+        leaf_display: &dyn Fn(&T) -> String,
+    ) {
+        let (color_start, color_end) = if color {
+            (DIRECTORY_COLOR, RESET_COLOR)
+        } else {
+            ("", "")
+        };
+        // Each child along with the prefix of its line and whether it is the last
+        // child of its level
+        let mut stack = vec![(TreeChild::Branch(self.arc()), String::new(), true)];
+        while let Some((child, branch_prefix, is_last_at_level)) = stack.pop() {
+            let gliph = if is_last_at_level {
                 LAST_CHILD_GLIPH
             } else {
-                ""
-            },
-            dir_name.unwrap_or_default()
-        );
-
-        buffer.push_str(&current_branch_display);
+                IS_CHILD_GLIPH
+            };
+            let branch = match child {
+                TreeChild::Branch(branch) => branch,
+                TreeChild::Leaf(leaf) => {
+                    let new_leaf_display =
+                        format!("{}{}{}\n", branch_prefix, gliph, leaf_display(&leaf));
+                    buffer.push_str(&new_leaf_display);
+                    continue;
+                }
+                omitted @ TreeChild::Omitted(_) => {
+                    buffer.push_str(&format!("{}{}{}\n", branch_prefix, gliph, omitted.name()));
+                    continue;
+                }
+            };
+            // The root is rendered without any gliph, whether or not it has a name
+            let is_root = branch.parent.is_none();
+            let current_branch_display = format!(
+                "{}{}{}/{}{}\n",
+                branch_prefix,
+                if is_root { "" } else { gliph },
+                color_start,
+                branch.label(),
+                color_end
+            );
+            buffer.push_str(&current_branch_display);
 
-        for (i, branch) in self.branches.lock().unwrap().iter().enumerate() {
-            let new_branch_prefix = format!(
+            let children_prefix = format!(
                 "{}{}",
                 branch_prefix,
-                if dir_name.is_none() {
+                if is_root {
                     ""
-                } else if i == branches_len - 1 && leaves_len == 0 && is_last_at_level {
+                } else if is_last_at_level {
                     SKIP_GLIPH_GAP
                 } else {
                     SKIP_GLIPH
                 }
             );
-
-            branch.build_string(
-                buffer,
-                &new_branch_prefix,
-                i == branches_len - 1 && leaves_len == 0,
-            );
+            let children = branch.children(order);
+            let children_len = children.len();
+            // Reversed, so that the first child is popped first
+            for (i, child) in children.into_iter().enumerate().rev() {
+                stack.push((child, children_prefix.clone(), i == children_len - 1));
+            }
         }
+    }
+}
 
-        for (i, leaf) in self.leaves.lock().unwrap().iter().enumerate() {
-            let new_leaf_display = format!(
-                "{}{}{}{}\n",
-                branch_prefix,
-                if dir_name.is_none() {
-                    ""
-                } else if !is_last_at_level {
-                    SKIP_GLIPH
-                } else {
-                    SKIP_GLIPH_GAP
-                },
-                if i == leaves_len - 1 {
-                    LAST_CHILD_GLIPH
-                } else {
-                    IS_CHILD_GLIPH
-                },
-                leaf.to_string()
-            );
+impl<T: Clone + PartialEq + Display> Tree<T> {
+    /// Generates a string representation of the tree structure, with the
+    /// directories in bold blue if `color` is set.
+    pub fn render(&self, color: bool, order: TreeOrder) -> String {
+        self.render_with(color, order, &|leaf| leaf.to_string())
+    }
+    /// Like [`Tree::render`], with the leaves rendered by `leaf_display` instead of
+    /// their [`Display`] implementation.
+    pub fn render_with(
+        &self,
+        color: bool,
+        order: TreeOrder,
+        leaf_display: &dyn Fn(&T) -> String,
+    ) -> String {
+        let mut buffer = String::new();
+        self.build_string(&mut buffer, color, order, leaf_display);
+        // Remove the last newline character
+        buffer.pop();
+        buffer
+    }
+}
+
+impl<T: Clone + PartialEq + Display> Tree<T> {
+    /// Renders the tree as nested HTML `<details>` elements, each directory being
+    /// collapsible, with the names escaped.
+    pub fn render_html(&self, order: TreeOrder) -> String {
+        let mut buffer = String::new();
+        self.build_html(&mut buffer, order);
+        buffer
+    }
 
-            buffer.push_str(&new_leaf_display);
+    /// Helper method to build the HTML representation of the tree.
+    ///
+    /// Like [`Tree::build_string`], the tree is walked depth first with an explicit
+    /// stack rather than recursively.
+    fn build_html(&self, buffer: &mut String, order: TreeOrder) {
+        let open_branch = |buffer: &mut String, branch: &Tree<T>| {
+            buffer.push_str(&format!(
+                "<details open>\n<summary>/{}</summary>\n<ul>\n",
+                utils::escape_xml(&branch.label())
+            ));
+        };
+        open_branch(buffer, self);
+        // Each child to render, or `None` to close the list of a branch; reversed, so
+        // that the first child is popped first
+        let mut stack = self
+            .children(order)
+            .into_iter()
+            .rev()
+            .map(Some)
+            .collect::<Vec<_>>();
+        while let Some(child) = stack.pop() {
+            let Some(child) = child else {
+                buffer.push_str("</ul>\n</details>\n</li>\n");
+                continue;
+            };
+            buffer.push_str("<li>");
+            match child {
+                TreeChild::Branch(branch) => {
+                    open_branch(buffer, &branch);
+                    stack.push(None);
+                    stack.extend(branch.children(order).into_iter().rev().map(Some));
+                    continue;
+                }
+                TreeChild::Leaf(leaf) => {
+                    buffer.push_str(&utils::escape_xml(&leaf.to_string()));
+                }
+                omitted @ TreeChild::Omitted(_) => {
+                    buffer.push_str(&utils::escape_xml(&omitted.name()));
+                }
+            }
+            buffer.push_str("\n</li>\n");
         }
+        buffer.push_str("</ul>\n</details>\n");
+    }
+}
+
+impl<T: Clone + PartialEq + Display> Display for Tree<T> {
+    /// Generates a string representation of the tree structure.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.render(false, TreeOrder::default()))
     }
 }
 
@@ -267,13 +669,40 @@ impl<T: Clone + PartialEq + Display> PartialEq for Tree<T> {
         let other_leaves = other.collect_all_leaves();
         // Test that all elements in self_leaves are present in other_leaves
         // And they both have the same parent
-        self_leaves.iter().all(|x| other_leaves.contains(&x)) && self.parent() == other.parent()
+        self_leaves.iter().all(|x| other_leaves.contains(x)) && self.parent() == other.parent()
+    }
+}
+
+impl<T: Clone + PartialEq + Display> Drop for Tree<T> {
+    /// Drops the branches that are not shared with another tree node one after the
+    /// other, rather than recursively, so that deep trees do not overflow the stack.
+    fn drop(&mut self) {
+        if Arc::strong_count(&self.branches) > 1 {
+            return;
+        }
+        let mut branches =
+            std::mem::take(&mut *self.branches.lock().unwrap_or_else(PoisonError::into_inner));
+        while let Some(branch) = branches.pop() {
+            if Arc::strong_count(&branch) == 1 && Arc::strong_count(&branch.branches) == 1 {
+                branches.append(
+                    &mut branch
+                        .branches
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner),
+                );
+            }
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::codebase::{
+        format::OutputFormat,
+        item::{CodebaseItem, FileContent},
+        Codebase,
+    };
     use std::{fs, ops::Deref, sync::Arc};
     use tempfile::TempDir;
 
@@ -376,6 +805,81 @@ mod tests {
         assert!(leaves.contains(&"leaf2".to_string()));
     }
 
+    /// Returns the root of a tree of `depth` nested branches, and its deepest branch,
+    /// holding the leaf made by `leaf` from the path of the branch.
+    fn deep_tree<T: Clone + PartialEq + Display>(
+        depth: usize,
+        leaf: impl FnOnce(&Path) -> T,
+    ) -> (Arc<Tree<T>>, Arc<Tree<T>>) {
+        let root = Tree::new(PathBuf::from("/root"), None);
+        let mut branch = root.clone();
+        for _ in 0..depth {
+            let child = Tree::new(
+                branch.current_dir().join("d"),
+                Some(Arc::downgrade(&branch)),
+            );
+            branch.add_branch(child.clone());
+            branch = child;
+        }
+        branch.add_leaf(leaf(branch.current_dir()));
+        (root, branch)
+    }
+
+    #[test]
+    fn test_deep_tree() {
+        const DEPTH: usize = 5000;
+        let (root, branch) = deep_tree(DEPTH, |_| "leaf".to_string());
+
+        let rendered = root.render(false, TreeOrder::DirsFirst);
+        assert_eq!(rendered.lines().count(), DEPTH + 2);
+        assert!(rendered.ends_with("└─ leaf"));
+        assert_eq!(root.collect_all_leaves(), ["leaf"]);
+        assert_eq!(root.collect_leaves_in_order(TreeOrder::Mixed), ["leaf"]);
+        assert!(branch.backtrack_to_branch(Path::new("/root")).is_some());
+        assert!(branch.gitignore().is_none());
+
+        let html = root.render_html(TreeOrder::DirsFirst);
+        assert_eq!(html.matches("<details open>").count(), DEPTH + 1);
+        assert_eq!(html.matches("</details>").count(), DEPTH + 1);
+        assert!(html.contains("<li>leaf\n</li>\n</ul>\n</details>\n</li>\n"));
+
+        let focused = root.focus(branch.current_dir(), &|_| false);
+        assert_eq!(focused.collect_all_leaves(), ["leaf"]);
+        assert_eq!(focused.render(false, TreeOrder::DirsFirst), rendered);
+
+        let filtered = root.filter_leaves(&|_| false);
+        assert!(filtered.collect_all_leaves().is_empty());
+        assert!(filtered.collect_local_branches().is_empty());
+        let filtered = root.filter_leaves(&|leaf| leaf == "leaf");
+        assert_eq!(filtered.render(false, TreeOrder::DirsFirst), rendered);
+
+        // The structured formats of a codebase of the same shape
+        let (root, _) = deep_tree(DEPTH, |dir| {
+            let leaf = CodebaseItem::new(dir.join("leaf"));
+            leaf.content
+                .set(FileContent::Text("content".to_string()))
+                .unwrap();
+            leaf
+        });
+        let codebase = Codebase::new(root);
+        for format in [
+            OutputFormat::Json,
+            OutputFormat::JsonLines,
+            OutputFormat::Xml,
+            OutputFormat::Html,
+        ] {
+            assert!(codebase
+                .try_to_string_as(format)
+                .unwrap()
+                .contains("content"));
+        }
+        let serializable = codebase.to_serializable();
+        assert_eq!(serializable.files.len(), 1);
+        let value: serde_json::Value =
+            serde_json::from_str(&codebase.try_to_string_as(OutputFormat::Json).unwrap()).unwrap();
+        assert_eq!(value["files"][0]["content"], "content");
+    }
+
     #[test]
     fn test_partial_eq() {
         let root_path = PathBuf::from("/");
@@ -396,7 +900,7 @@ mod tests {
         fs::write(&gitignore_path, "*.rs").expect("Unable to write to .gitignore");
 
         // Create GitIgnore from the temporary path
-        let gitignore = GitIgnore::from(&gitignore_path)
+        let gitignore = GitIgnore::from(&gitignore_path, &[])
             .expect("Failed to create GitIgnore")
             .expect("GitIgnore is None");
 
@@ -418,7 +922,7 @@ mod tests {
         fs::write(&gitignore_path, "*.rs").expect("Unable to write to .gitignore");
 
         // Create GitIgnore from the temporary path
-        let gitignore = GitIgnore::from(&gitignore_path)
+        let gitignore = GitIgnore::from(&gitignore_path, &[])
             .expect("Failed to create GitIgnore")
             .expect("GitIgnore is None");
 
@@ -453,6 +957,214 @@ mod tests {
         assert_eq!(output, expected);
     }
 
+    #[test]
+    fn test_tree_focus() {
+        let root_path = PathBuf::from("/project");
+        let tree = Arc::new(Tree::new(root_path.clone(), None));
+        tree.add_leaf("README.md".to_string());
+        for name in ["docs", "src"] {
+            let branch = Tree::new(root_path.join(name), Some(Arc::downgrade(&tree)));
+            branch.add_leaf(format!("{}.txt", name));
+            let nested = Tree::new(
+                root_path.join(name).join("nested"),
+                Some(Arc::downgrade(&branch)),
+            );
+            nested.add_leaf("deep.txt".to_string());
+            branch.add_branch(nested);
+            tree.add_branch(branch);
+        }
+
+        let focused = tree.focus(&root_path.join("src").join("nested"), &|_| false);
+        assert_eq!(
+            focused.to_string(),
+            "/project\n└─ /src\n   └─ /nested\n      └─ deep.txt"
+        );
+        let focused = tree.focus(&root_path.join("src"), &|_| false);
+        assert_eq!(
+            focused.to_string(),
+            "/project\n└─ /src\n   ├─ /nested\n   │  └─ deep.txt\n   └─ src.txt"
+        );
+        // A file is focused on through the leaves of its parent
+        let focused = tree.focus(&root_path.join("README.md"), &|leaf| leaf == "README.md");
+        assert_eq!(focused.to_string(), "/project\n└─ README.md");
+    }
+
+    #[test]
+    fn test_tree_render_with() {
+        let tree = Arc::new(Tree::new(PathBuf::from("/"), None));
+        tree.add_leaf("leaf1".to_string());
+
+        let output = tree.render_with(false, TreeOrder::default(), &|leaf| leaf.to_uppercase());
+
+        assert_eq!(output, "/\n└─ LEAF1");
+    }
+
+    #[test]
+    fn test_named_root_to_string() {
+        let root_path = PathBuf::from("/project");
+        let tree = Arc::new(Tree::new(root_path.clone(), None));
+        tree.add_leaf("leaf1".to_string());
+
+        let branch_path = PathBuf::from("/project/branch");
+        let branch = Tree::new(branch_path.clone(), Some(Arc::downgrade(&tree)));
+        branch.add_leaf("leaf2".to_string());
+
+        tree.add_branch(branch.clone());
+
+        let expected = "/project\n├─ /branch\n│  └─ leaf2\n└─ leaf1";
+        let output = tree.to_string();
+
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn test_labeled_root_to_string() {
+        let root_path = PathBuf::from("/project");
+        let tree = Arc::new(Tree::new(root_path.clone(), None));
+        tree.add_leaf("leaf1".to_string());
+        tree.set_label("myproject".to_string());
+
+        assert_eq!(tree.label(), "myproject");
+        assert_eq!(tree.to_string(), "/myproject\n└─ leaf1");
+    }
+
+    #[test]
+    fn test_colored_tree_render() {
+        let root_path = PathBuf::from("/project");
+        let tree = Arc::new(Tree::new(root_path.clone(), None));
+        tree.add_leaf("leaf1".to_string());
+
+        let branch_path = PathBuf::from("/project/branch");
+        let branch = Tree::new(branch_path.clone(), Some(Arc::downgrade(&tree)));
+        branch.add_leaf("leaf2".to_string());
+
+        tree.add_branch(branch.clone());
+
+        let expected =
+            "\x1b[1;34m/project\x1b[0m\n├─ \x1b[1;34m/branch\x1b[0m\n│  └─ leaf2\n└─ leaf1";
+        assert_eq!(tree.render(true, TreeOrder::DirsFirst), expected);
+        assert_eq!(tree.render(false, TreeOrder::DirsFirst), tree.to_string());
+    }
+
+    #[test]
+    fn test_stable_sort_key() {
+        // "é" as a single code point, and as "e" followed by a combining accent
+        let composed = OsStr::new("caf\u{e9}.txt");
+        let decomposed = OsStr::new("cafe\u{301}.txt");
+        assert_ne!(composed, decomposed);
+        assert_eq!(
+            StableSort::Nfc.key(composed),
+            StableSort::Nfc.key(decomposed)
+        );
+        assert_eq!(StableSort::Nfc.key(OsStr::new("README.md")), "README.md");
+        assert_eq!(
+            StableSort::NfcLowercase.key(OsStr::new("README.md")),
+            "readme.md"
+        );
+    }
+
+    #[test]
+    fn test_stable_sort_compare() {
+        // Equal under a case-insensitive collation, and under Unicode normalization
+        for (a, b) in [("README.md", "readme.md"), ("caf\u{e9}", "cafe\u{301}")] {
+            let (a, b) = (OsStr::new(a), OsStr::new(b));
+            assert_eq!(
+                StableSort::NfcLowercase.key(a),
+                StableSort::NfcLowercase.key(b)
+            );
+            let ordering = StableSort::NfcLowercase.compare(a, b);
+            assert_ne!(ordering, Ordering::Equal);
+            assert_eq!(StableSort::NfcLowercase.compare(b, a), ordering.reverse());
+        }
+        assert_eq!(
+            StableSort::Nfc.compare(OsStr::new("a"), OsStr::new("b")),
+            Ordering::Less
+        );
+    }
+
+    #[test]
+    fn test_mixed_tree_order_is_deterministic() {
+        let render = |names: [&str; 2]| {
+            let tree = Arc::new(Tree::new(PathBuf::from("/"), None));
+            for name in names {
+                tree.add_leaf(name.to_string());
+            }
+            tree.render(false, TreeOrder::Mixed)
+        };
+        let (composed, decomposed) = ("caf\u{e9}", "cafe\u{301}");
+        assert_eq!(
+            render([composed, decomposed]),
+            render([decomposed, composed])
+        );
+    }
+
+    #[test]
+    fn test_tree_order() {
+        let root_path = PathBuf::from("/project");
+        let tree = Arc::new(Tree::new(root_path.clone(), None));
+        tree.add_leaf("a.txt".to_string());
+        tree.add_leaf("c.txt".to_string());
+
+        let branch = Tree::new(root_path.join("b"), Some(Arc::downgrade(&tree)));
+        for name in ["x", "y"] {
+            let nested = Tree::new(
+                root_path.join("b").join(name),
+                Some(Arc::downgrade(&branch)),
+            );
+            nested.add_leaf(format!("{}.rs", name));
+            branch.add_branch(nested);
+        }
+        tree.add_branch(branch);
+
+        assert_eq!(
+            tree.render(false, TreeOrder::DirsFirst),
+            "/project\n├─ /b\n│  ├─ /x\n│  │  └─ x.rs\n│  └─ /y\n│     └─ y.rs\n├─ a.txt\n└─ c.txt"
+        );
+        assert_eq!(
+            tree.render(false, TreeOrder::FilesFirst),
+            "/project\n├─ a.txt\n├─ c.txt\n└─ /b\n   ├─ /x\n   │  └─ x.rs\n   └─ /y\n      └─ y.rs"
+        );
+        assert_eq!(
+            tree.render(false, TreeOrder::Mixed),
+            "/project\n├─ a.txt\n├─ /b\n│  ├─ /x\n│  │  └─ x.rs\n│  └─ /y\n│     └─ y.rs\n└─ c.txt"
+        );
+        assert_eq!(
+            tree.collect_leaves_in_order(TreeOrder::Mixed),
+            vec!["a.txt", "x.rs", "y.rs", "c.txt"]
+        );
+        assert_eq!(
+            tree.collect_leaves_in_order(TreeOrder::FilesFirst),
+            tree.collect_all_leaves()
+        );
+    }
+
+    #[test]
+    fn test_tree_omitted_leaves() {
+        let root_path = PathBuf::from("/project");
+        let tree = Arc::new(Tree::new(root_path.clone(), None));
+        tree.add_leaf("a.txt".to_string());
+        tree.omit_leaf();
+        let branch = Tree::new(root_path.join("b"), Some(Arc::downgrade(&tree)));
+        branch.add_leaf("b.txt".to_string());
+        tree.add_branch(branch);
+
+        assert_eq!(tree.omitted_leaves(), 1);
+        assert_eq!(
+            tree.render(false, TreeOrder::FilesFirst),
+            "/project\n├─ a.txt\n├─ ... (1 more file omitted)\n└─ /b\n   └─ b.txt"
+        );
+        assert_eq!(
+            tree.render(false, TreeOrder::Mixed),
+            "/project\n├─ a.txt\n├─ /b\n│  └─ b.txt\n└─ ... (1 more file omitted)"
+        );
+        // Not a leaf itself, and kept when filtering
+        assert_eq!(
+            tree.collect_leaves_in_order(TreeOrder::DirsFirst),
+            vec!["b.txt", "a.txt"]
+        );
+        assert_eq!(tree.filter_leaves(&|_| true).omitted_leaves(), 1);
+    }
+
     #[test]
     fn test_tree_with_multiple_branches_and_leaves() {
         let root_path = PathBuf::from("/");
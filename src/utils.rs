@@ -1,3 +1,29 @@
+use std::{
+    borrow::Cow,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use globset::{Glob, GlobBuilder};
+use sha2::{Digest, Sha256};
+
+use crate::error::{CunwError, CunwErrorKind, Result};
+
+/// Decimal units, as used by [`format_size`].
+const DECIMAL_UNITS: [(&str, u64); 4] = [
+    ("TB", 1_000_000_000_000),
+    ("GB", 1_000_000_000),
+    ("MB", 1_000_000),
+    ("KB", 1_000),
+];
+/// Binary units.
+const BINARY_UNITS: [(&str, u64); 4] = [
+    ("TIB", 1 << 40),
+    ("GIB", 1 << 30),
+    ("MIB", 1 << 20),
+    ("KIB", 1 << 10),
+];
+
 /// Checks if the given `snippet` starts with any of the provided `prefixes`.
 ///
 /// # Arguments
@@ -8,23 +34,22 @@
 /// # Returns
 ///
 /// * `Some(prefix)` if the `snippet` starts with any of the `prefixes`, where `prefix` is the
-///  first matching prefix.
+///   first matching prefix.
 /// * `None` otherwise.
 ///
 /// # Examples
 ///
 /// ```
+/// # use cunw::utils::start_with_one_of;
 /// let snippet = "hello world";
 /// let prefixes = ["he", "wo"];
 /// assert_eq!(start_with_one_of(snippet, &prefixes), Some("he"));
 /// ```
 pub fn start_with_one_of<'a>(snippet: &str, prefixes: &[&'a str]) -> Option<&'a str> {
-    for prefix in prefixes {
-        if snippet.starts_with(prefix) {
-            return Some(prefix);
-        }
-    }
-    None
+    prefixes
+        .iter()
+        .find(|prefix| snippet.starts_with(*prefix))
+        .copied()
 }
 
 /// Checks if the given `snippet` ends with any of the provided `suffixes`.
@@ -37,21 +62,667 @@ pub fn start_with_one_of<'a>(snippet: &str, prefixes: &[&'a str]) -> Option<&'a
 /// # Returns
 ///
 /// * `Some(suffix)` if the `snippet` ends with any of the `suffixes`, where `suffix` is the
-/// first matching suffix.
+///   first matching suffix.
 /// * `None` otherwise.
 ///
 /// # Examples
 ///
 /// ```
+/// # use cunw::utils::end_with_one_of;
 /// let snippet = "hello world";
 /// let suffixes = ["ld", "lo"];
 /// assert_eq!(end_with_one_of(snippet, &suffixes), Some("ld"));
 /// ```
 pub fn end_with_one_of<'a>(snippet: &str, suffixes: &[&'a str]) -> Option<&'a str> {
-    for suffix in suffixes {
-        if snippet.ends_with(suffix) {
-            return Some(suffix);
+    suffixes
+        .iter()
+        .find(|suffix| snippet.ends_with(*suffix))
+        .copied()
+}
+
+/// Estimates the number of tokens a LLM would use to represent `text`.
+///
+/// This uses the common approximation of one token per 4 characters,
+/// which is good enough to size the output without depending on a tokenizer.
+///
+/// # Examples
+///
+/// ```
+/// # use cunw::utils::estimate_tokens;
+/// assert_eq!(estimate_tokens("hello world!"), 3);
+/// ```
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(4)
+}
+
+/// Truncates the lines of `text` longer than `max_length` characters to `max_length`
+/// characters, followed by a ` …(+N chars)` marker telling how many were removed.
+///
+/// Lengths are counted in characters, so multibyte characters are never split.
+///
+/// # Examples
+///
+/// ```
+/// # use cunw::utils::truncate_long_lines;
+/// assert_eq!(truncate_long_lines("abcdef\nab", 4), "abcd …(+2 chars)\nab");
+/// ```
+pub fn truncate_long_lines(text: &str, max_length: usize) -> Cow<'_, str> {
+    // Fast path: no line can be longer than the whole text
+    if text.len() <= max_length {
+        return Cow::Borrowed(text);
+    }
+
+    let mut truncated = String::with_capacity(text.len());
+    let mut any_truncated = false;
+    for line in text.split_inclusive('\n') {
+        let content = line.trim_end_matches(['\r', '\n']);
+        let line_ending = &line[content.len()..];
+        match content.char_indices().nth(max_length) {
+            Some((end, _)) => {
+                any_truncated = true;
+                let removed = content[end..].chars().count();
+                truncated.push_str(&content[..end]);
+                truncated.push_str(&format!(" …(+{} chars)", removed));
+                truncated.push_str(line_ending);
+            }
+            None => truncated.push_str(line),
+        }
+    }
+    if any_truncated {
+        Cow::Owned(truncated)
+    } else {
+        Cow::Borrowed(text)
+    }
+}
+
+/// Expands the tabs of the leading whitespace of each line of `text` to spaces, up to
+/// the next multiple of `tab_width` columns. The tabs after the indentation are kept.
+///
+/// # Examples
+///
+/// ```
+/// # use cunw::utils::expand_leading_tabs;
+/// assert_eq!(expand_leading_tabs("\tif a {\n\t\tb\t// c\n", 4), "    if a {\n        b\t// c\n");
+/// ```
+pub fn expand_leading_tabs(text: &str, tab_width: usize) -> Cow<'_, str> {
+    if !text.contains('\t') {
+        return Cow::Borrowed(text);
+    }
+
+    let mut expanded = String::with_capacity(text.len());
+    let mut any_expanded = false;
+    for line in text.split_inclusive('\n') {
+        let content = line.trim_start_matches([' ', '\t']);
+        let indentation = &line[..line.len() - content.len()];
+        if !indentation.contains('\t') {
+            expanded.push_str(line);
+            continue;
+        }
+        any_expanded = true;
+        let mut column = 0;
+        for c in indentation.chars() {
+            let width = if c == '\t' {
+                tab_width - column % tab_width
+            } else {
+                1
+            };
+            expanded.extend(std::iter::repeat_n(' ', width));
+            column += width;
+        }
+        expanded.push_str(content);
+    }
+    if any_expanded {
+        Cow::Owned(expanded)
+    } else {
+        Cow::Borrowed(text)
+    }
+}
+
+/// Converts the `\r\n` and `\r` line endings of `text` to `\n`, and strips
+/// a leading byte order mark.
+///
+/// # Examples
+///
+/// ```
+/// # use cunw::utils::normalize_newlines;
+/// assert_eq!(normalize_newlines("\u{feff}a\r\nb\rc\n"), "a\nb\nc\n");
+/// ```
+pub fn normalize_newlines(text: &str) -> Cow<'_, str> {
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    if !text.contains('\r') {
+        return Cow::Borrowed(text);
+    }
+    Cow::Owned(text.replace("\r\n", "\n").replace('\r', "\n"))
+}
+
+/// Escapes `text` to be used as the content or an attribute of an XML element.
+///
+/// Carriage returns are escaped, since parsers would otherwise normalize them, and the
+/// characters that XML 1.0 does not allow at all (e.g. `\0`) are replaced by `U+FFFD`.
+///
+/// # Examples
+///
+/// ```
+/// # use cunw::utils::escape_xml;
+/// assert_eq!(escape_xml("a < \"b\" & c"), "a &lt; &quot;b&quot; &amp; c");
+/// ```
+pub fn escape_xml(text: &str) -> Cow<'_, str> {
+    let is_allowed =
+        |c: char| matches!(c, '\t' | '\n') || (c >= ' ' && c != '\u{fffe}' && c != '\u{ffff}');
+    let needs_escape = |c: char| matches!(c, '&' | '<' | '>' | '"' | '\'') || !is_allowed(c);
+    if !text.contains(needs_escape) {
+        return Cow::Borrowed(text);
+    }
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\r' => escaped.push_str("&#13;"),
+            c if !is_allowed(c) => escaped.push('\u{fffd}'),
+            c => escaped.push(c),
+        }
+    }
+    Cow::Owned(escaped)
+}
+
+/// Parses a human readable size, such as `500`, `500B`, `1.5MB` or `2GiB`, into bytes.
+///
+/// Units are case insensitive. `KB`, `MB`, `GB` and `TB` are decimal (powers of 1000),
+/// while `KiB`, `MiB`, `GiB` and `TiB` are binary (powers of 1024). A number without
+/// unit is a number of bytes.
+///
+/// # Examples
+///
+/// ```
+/// # use cunw::utils::parse_size;
+/// assert_eq!(parse_size("1MB").unwrap(), 1_000_000);
+/// assert_eq!(parse_size("1MiB").unwrap(), 1_048_576);
+/// ```
+pub fn parse_size(s: &str) -> Result<u64> {
+    let invalid = || {
+        CunwError::new(CunwErrorKind::InvalidArgument(format!(
+            "Invalid size '{}', expected a number of bytes optionally followed by a unit (e.g. 500KB, 1MB, 2GiB)",
+            s
+        )))
+    };
+    let trimmed = s.trim();
+    let unit_start = trimmed
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(trimmed.len());
+    let (number, unit) = trimmed.split_at(unit_start);
+    let unit = unit.trim().to_ascii_uppercase();
+    let multiplier = match unit.as_str() {
+        "" | "B" => 1,
+        unit => BINARY_UNITS
+            .iter()
+            .chain(DECIMAL_UNITS.iter())
+            .find(|(name, _)| *name == unit)
+            .map(|(_, multiplier)| *multiplier)
+            .ok_or_else(invalid)?,
+    };
+    // Avoid going through floats for plain integers, which would lose precision
+    if let Ok(number) = number.parse::<u64>() {
+        return number.checked_mul(multiplier).ok_or_else(invalid);
+    }
+    let number = number.parse::<f64>().map_err(|_| invalid())?;
+    let bytes = number * multiplier as f64;
+    if !bytes.is_finite() || bytes > u64::MAX as f64 {
+        return Err(invalid());
+    }
+    Ok(bytes.round() as u64)
+}
+
+/// Parses a duration given as a number followed by a unit, `s` (seconds), `m`
+/// (minutes), `h` (hours), `d` (days) or `w` (weeks).
+///
+/// # Examples
+///
+/// ```
+/// # use std::time::Duration;
+/// # use cunw::utils::parse_duration;
+/// assert_eq!(parse_duration("30m").unwrap(), Duration::from_secs(30 * 60));
+/// assert_eq!(parse_duration("7d").unwrap(), Duration::from_secs(7 * 24 * 60 * 60));
+/// ```
+pub fn parse_duration(s: &str) -> Result<Duration> {
+    let invalid = || {
+        CunwError::new(CunwErrorKind::InvalidArgument(format!(
+            "Invalid duration '{}', expected a number followed by a unit (e.g. 30m, 2h, 7d)",
+            s
+        )))
+    };
+    let trimmed = s.trim();
+    let unit_start = trimmed
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(trimmed.len());
+    let (number, unit) = trimmed.split_at(unit_start);
+    let seconds_per_unit = match unit.trim().to_ascii_lowercase().as_str() {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return Err(invalid()),
+    };
+    let number = number.parse::<u64>().map_err(|_| invalid())?;
+    let seconds = number.checked_mul(seconds_per_unit).ok_or_else(invalid)?;
+    Ok(Duration::from_secs(seconds))
+}
+
+/// Removes the ANSI escape sequences (e.g. colors) from `s`.
+///
+/// # Examples
+///
+/// ```
+/// # use cunw::utils::strip_ansi_escapes;
+/// assert_eq!(strip_ansi_escapes("\x1b[1;34msrc\x1b[0m/"), "src/");
+/// ```
+pub fn strip_ansi_escapes(s: &str) -> Cow<'_, str> {
+    if !s.contains('\x1b') {
+        return Cow::Borrowed(s);
+    }
+    let mut stripped = String::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            stripped.push(c);
+            continue;
+        }
+        // A CSI sequence ends with a byte in the '@'..='~' range
+        if chars.next_if_eq(&'[').is_some() {
+            for c in chars.by_ref() {
+                if ('@'..='~').contains(&c) {
+                    break;
+                }
+            }
+        }
+    }
+    Cow::Owned(stripped)
+}
+
+/// Parses the `\n`, `\t` and `\\` escapes of `s`, so that separators containing
+/// newlines can be passed on the command line.
+///
+/// # Examples
+///
+/// ```
+/// # use cunw::utils::parse_escapes;
+/// assert_eq!(parse_escapes("\\n---\\n").unwrap(), "\n---\n");
+/// ```
+pub fn parse_escapes(s: &str) -> Result<String> {
+    let mut parsed = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            parsed.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => parsed.push('\n'),
+            Some('t') => parsed.push('\t'),
+            Some('\\') => parsed.push('\\'),
+            other => {
+                return Err(CunwError::new(CunwErrorKind::InvalidArgument(format!(
+                    "Invalid escape '\\{}' in '{}', expected \\n, \\t or \\\\",
+                    other.map(String::from).unwrap_or_default(),
+                    s
+                ))))
+            }
+        }
+    }
+    Ok(parsed)
+}
+
+/// Replaces the `{name}` placeholders of `template` with the values returned by
+/// `value`, `{{` and `}}` standing for literal braces.
+///
+/// Returns an error for a brace that is not closed, or a placeholder for which
+/// `value` returns [`None`].
+///
+/// # Examples
+///
+/// ```
+/// # use cunw::utils::render_template;
+/// let rendered = render_template("{{{name}}}", |name| (name == "name").then(|| "a".into()));
+/// assert_eq!(rendered.unwrap(), "{a}");
+/// ```
+pub fn render_template<'a>(
+    template: &str,
+    value: impl Fn(&str) -> Option<Cow<'a, str>>,
+) -> Result<String> {
+    let invalid = |message: String| {
+        CunwError::new(CunwErrorKind::InvalidArgument(format!(
+            "{} in the template '{}'",
+            message, template
+        )))
+    };
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(i) = rest.find(['{', '}']) {
+        rendered.push_str(&rest[..i]);
+        let brace = &rest[i..i + 1];
+        rest = &rest[i + 1..];
+        if let Some(after) = rest.strip_prefix(brace) {
+            rendered.push_str(brace);
+            rest = after;
+        } else if brace == "}" {
+            return Err(invalid("Unmatched '}'".to_string()));
+        } else {
+            let end = rest
+                .find('}')
+                .ok_or_else(|| invalid("Unclosed '{'".to_string()))?;
+            let name = &rest[..end];
+            let name_value = value(name)
+                .ok_or_else(|| invalid(format!("Unknown placeholder '{{{}}}'", name)))?;
+            rendered.push_str(&name_value);
+            rest = &rest[end + 1..];
+        }
+    }
+    rendered.push_str(rest);
+    Ok(rendered)
+}
+
+/// Returns the output name used with `--name-output-after-dir` for the scanned
+/// directory `root`, e.g. `myproject.cunw.txt`, or [`None`] if it has no name (e.g. `/`).
+pub fn output_name_for_dir(root: &Path) -> Option<PathBuf> {
+    let name = root.file_name()?;
+    Some(PathBuf::from(format!(
+        "{}.cunw.txt",
+        name.to_string_lossy()
+    )))
+}
+
+/// Returns the default concurrency of the reads, the available parallelism of the
+/// machine (or 1 if it cannot be queried).
+pub fn default_concurrency() -> usize {
+    std::thread::available_parallelism().map_or(1, |parallelism| parallelism.get())
+}
+
+/// Parses a maximum depth, which must be at least 1.
+pub fn parse_max_depth(s: &str) -> Result<usize> {
+    let max_depth = s.trim().parse::<usize>().map_err(|_| {
+        CunwError::new(CunwErrorKind::InvalidArgument(format!(
+            "Invalid max depth '{}', expected a positive number",
+            s
+        )))
+    })?;
+    check_max_depth(max_depth)
+}
+
+/// Rejects a maximum depth of 0: the root is at depth 0, so the walk would not list
+/// anything.
+pub fn check_max_depth(max_depth: usize) -> Result<usize> {
+    if max_depth == 0 {
+        return Err(CunwError::new(CunwErrorKind::InvalidArgument(
+            "A max depth of 0 would not list anything, use 1 to only list the direct entries of the scanned directory".to_string(),
+        )));
+    }
+    Ok(max_depth)
+}
+
+/// Normalizes a glob so that it matches the paths yielded by the walk.
+///
+/// The walk matches globs against paths relative to the (canonicalized) root and
+/// without a trailing `/`, so `./target`, `././target` and `target/` are all
+/// equivalent to `target`.
+pub fn normalize_glob(glob: &str) -> String {
+    let mut glob = glob;
+    while let Some(stripped) = glob.strip_prefix("./") {
+        glob = stripped;
+    }
+    if glob.len() > 1 {
+        glob = glob.strip_suffix('/').unwrap_or(glob);
+    }
+    glob.to_string()
+}
+
+/// Parses a user given glob pattern, with the same syntax on every platform.
+///
+/// Supports `?`, `*` (which also matches `/`), `**`, `{a,b}` alternations,
+/// `[0-9]` and `[!0-9]` character classes, and `\` to escape a special character.
+///
+/// # Examples
+///
+/// ```
+/// # use cunw::utils::parse_glob;
+/// assert!(parse_glob("**/*.{png,jpg}").unwrap().compile_matcher().is_match("img/a.png"));
+/// ```
+pub fn parse_glob(pattern: &str) -> std::result::Result<Glob, globset::Error> {
+    GlobBuilder::new(pattern)
+        .literal_separator(false)
+        .backslash_escape(true)
+        .build()
+}
+
+/// Formats a size in bytes in a human readable way, using decimal units.
+///
+/// # Examples
+///
+/// ```
+/// # use cunw::utils::format_size;
+/// assert_eq!(format_size(4_200_000), "4.2MB");
+/// ```
+pub fn format_size(bytes: u64) -> String {
+    for (unit, multiplier) in DECIMAL_UNITS {
+        if bytes >= multiplier {
+            let value = format!("{:.1}", bytes as f64 / multiplier as f64);
+            let value = value.strip_suffix(".0").unwrap_or(&value);
+            return format!("{}{}", value, unit);
         }
     }
-    None
+    format!("{}B", bytes)
+}
+
+/// Returns the SHA-256 of `bytes`, as lowercase hexadecimal.
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape_xml() {
+        assert!(matches!(escape_xml("fn main() {}\n"), Cow::Borrowed(_)));
+        assert_eq!(
+            escape_xml("if a < b && c > 'd' {\"e\"}"),
+            "if a &lt; b &amp;&amp; c &gt; &apos;d&apos; {&quot;e&quot;}"
+        );
+        assert_eq!(escape_xml("a\r\n\tb\0c"), "a&#13;\n\tb\u{fffd}c");
+    }
+
+    #[test]
+    fn test_truncate_long_lines() {
+        assert_eq!(truncate_long_lines("short\nlines\n", 5), "short\nlines\n");
+        assert_eq!(
+            truncate_long_lines("abcdef\r\nab\nabcdefgh", 4),
+            "abcd …(+2 chars)\r\nab\nabcd …(+4 chars)"
+        );
+        assert!(matches!(
+            truncate_long_lines("abc\nabc", 3),
+            Cow::Borrowed(_)
+        ));
+    }
+
+    #[test]
+    fn test_parse_size_decimal_units() {
+        assert_eq!(parse_size("500").unwrap(), 500);
+        assert_eq!(parse_size("500B").unwrap(), 500);
+        assert_eq!(parse_size("500KB").unwrap(), 500_000);
+        assert_eq!(parse_size("1MB").unwrap(), 1_000_000);
+        assert_eq!(parse_size("1.5mb").unwrap(), 1_500_000);
+        assert_eq!(parse_size("2 GB").unwrap(), 2_000_000_000);
+        assert_eq!(parse_size("1TB").unwrap(), 1_000_000_000_000);
+    }
+
+    #[test]
+    fn test_parse_size_binary_units() {
+        assert_eq!(parse_size("1KiB").unwrap(), 1024);
+        assert_eq!(parse_size("1MiB").unwrap(), 1_048_576);
+        assert_eq!(parse_size("2GiB").unwrap(), 2_147_483_648);
+        assert_eq!(parse_size("0.5kib").unwrap(), 512);
+    }
+
+    #[test]
+    fn test_parse_size_invalid() {
+        assert!(parse_size("").is_err());
+        assert!(parse_size("MB").is_err());
+        assert!(parse_size("12XB").is_err());
+        assert!(parse_size("-1").is_err());
+        assert!(parse_size("99999999999TB").is_err());
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("45s").unwrap(), Duration::from_secs(45));
+        assert_eq!(parse_duration("30m").unwrap(), Duration::from_secs(1800));
+        assert_eq!(parse_duration("2h").unwrap(), Duration::from_secs(7200));
+        assert_eq!(parse_duration("7D").unwrap(), Duration::from_secs(604_800));
+        assert_eq!(parse_duration("1w").unwrap(), Duration::from_secs(604_800));
+        assert!(parse_duration("7").is_err());
+        assert!(parse_duration("d").is_err());
+        assert!(parse_duration("1.5h").is_err());
+        assert!(parse_duration("-1d").is_err());
+        assert!(parse_duration("99999999999999999999w").is_err());
+    }
+
+    #[test]
+    fn test_strip_ansi_escapes() {
+        assert_eq!(strip_ansi_escapes("plain"), "plain");
+        assert_eq!(
+            strip_ansi_escapes(
+                "\x1b[2min \x1b[4msrc/main.rs\x1b[0m 1:1\x1b[0m \x1b[32mDone\x1b[0m"
+            ),
+            "in src/main.rs 1:1 Done"
+        );
+    }
+
+    #[test]
+    fn test_parse_escapes() {
+        assert_eq!(parse_escapes("plain").unwrap(), "plain");
+        assert_eq!(parse_escapes("\\n---\\n").unwrap(), "\n---\n");
+        assert_eq!(parse_escapes("a\\tb\\\\n").unwrap(), "a\tb\\n");
+        assert!(parse_escapes("\\x").is_err());
+        assert!(parse_escapes("trailing\\").is_err());
+    }
+
+    #[test]
+    fn test_render_template() {
+        let value = |name: &str| match name {
+            "path" => Some(Cow::Borrowed("src/main.rs")),
+            "lines" => Some(Cow::Owned(2.to_string())),
+            _ => None,
+        };
+        assert_eq!(
+            render_template("### {path} ({lines} lines)", value).unwrap(),
+            "### src/main.rs (2 lines)"
+        );
+        assert_eq!(
+            render_template("{{path}} {{{path}}}", value).unwrap(),
+            "{path} {src/main.rs}"
+        );
+        assert!(render_template("{content}", value).is_err());
+        assert!(render_template("{path", value).is_err());
+        assert!(render_template("path}", value).is_err());
+    }
+
+    #[test]
+    fn test_output_name_for_dir() {
+        assert_eq!(
+            output_name_for_dir(Path::new("/home/me/myproject")),
+            Some(PathBuf::from("myproject.cunw.txt"))
+        );
+        assert_eq!(output_name_for_dir(Path::new("/")), None);
+    }
+
+    #[test]
+    fn test_parse_max_depth() {
+        assert_eq!(parse_max_depth("1").unwrap(), 1);
+        assert_eq!(parse_max_depth("12").unwrap(), 12);
+        assert!(parse_max_depth("0").is_err());
+        assert!(parse_max_depth("-1").is_err());
+        assert!(parse_max_depth("deep").is_err());
+    }
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(512), "512B");
+        assert_eq!(format_size(1_000_000), "1MB");
+        assert_eq!(format_size(4_200_000), "4.2MB");
+        assert_eq!(format_size(1_536), "1.5KB");
+    }
+
+    #[test]
+    fn test_sha256_hex() {
+        assert_eq!(
+            sha256_hex(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn test_expand_leading_tabs() {
+        assert_eq!(expand_leading_tabs("\ta\n\t\tb\n", 2), "  a\n    b\n");
+        // Up to the next tab stop
+        assert_eq!(expand_leading_tabs("  \ta\n", 4), "    a\n");
+        assert_eq!(expand_leading_tabs("a\tb\n\t\r\n", 4), "a\tb\n    \r\n");
+        assert!(matches!(
+            expand_leading_tabs("a\tb\n  c", 4),
+            Cow::Borrowed(_)
+        ));
+    }
+
+    #[test]
+    fn test_normalize_newlines() {
+        assert_eq!(normalize_newlines("a\r\nb\r\n"), "a\nb\n");
+        assert_eq!(normalize_newlines("a\rb\r\n\r\nc"), "a\nb\n\nc");
+        assert_eq!(normalize_newlines("\u{feff}a\nb"), "a\nb");
+        assert_eq!(normalize_newlines("a\u{feff}b"), "a\u{feff}b");
+        assert!(matches!(normalize_newlines("a\nb"), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_normalize_glob() {
+        assert_eq!(normalize_glob("target"), "target");
+        assert_eq!(normalize_glob("./target"), "target");
+        assert_eq!(normalize_glob("././target"), "target");
+        assert_eq!(normalize_glob("./target/"), "target");
+        assert_eq!(normalize_glob("target/"), "target");
+        assert_eq!(normalize_glob("**/build/"), "**/build");
+        assert_eq!(normalize_glob("../target"), "../target");
+        assert_eq!(normalize_glob("/"), "/");
+    }
+
+    #[test]
+    fn test_parse_glob() {
+        let is_match = |pattern: &str, path: &str| {
+            parse_glob(pattern)
+                .unwrap()
+                .compile_matcher()
+                .is_match(path)
+        };
+        assert!(is_match("**/*.{png,jpg}", "img/a.png"));
+        assert!(is_match("**/*.{png,jpg}", "b.jpg"));
+        assert!(!is_match("**/*.{png,jpg}", "img/c.gif"));
+        assert!(is_match("part[0-9].txt", "part1.txt"));
+        assert!(!is_match("part[0-9].txt", "parta.txt"));
+        assert!(is_match("part[!0-9].txt", "parta.txt"));
+        assert!(is_match("src/*.rs", "src/nested/main.rs"));
+        assert!(is_match("\\*.txt", "*.txt"));
+        assert!(!is_match("\\*.txt", "a.txt"));
+        assert!(parse_glob("{unclosed").is_err());
+    }
+
+    #[test]
+    fn test_truncate_long_lines_multibyte() {
+        assert_eq!(truncate_long_lines("ééééé", 2), "éé …(+3 chars)");
+        assert_eq!(truncate_long_lines("日本語", 3), "日本語");
+    }
 }
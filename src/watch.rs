@@ -0,0 +1,424 @@
+use std::{
+    collections::HashSet,
+    future::Future,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use globset::GlobSet;
+use notify::{
+    event::{MetadataKind, ModifyKind},
+    Event, EventKind, RecursiveMode, Watcher,
+};
+use tokio::sync::mpsc;
+
+use crate::{
+    error::{CunwError, CunwErrorKind, Result},
+    gitignore::{GitIgnore, GitIgnoreCache, DEFAULT_IGNORE_FILENAME},
+    logger::Logger,
+};
+
+/// How long to wait for more changes before regenerating the output.
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Decides whether a change under the watched directory should trigger a rebuild,
+/// following the same ignore rules as the walk.
+pub struct ChangeFilter {
+    root: PathBuf,
+    excluded_paths: GlobSet,
+    /// The patterns that only exclude directories.
+    excluded_dirs: GlobSet,
+    /// The patterns that only exclude files.
+    excluded_files: GlobSet,
+    /// The names of the files that the patterns do not exclude.
+    included_names: HashSet<String>,
+    consider_gitignores: bool,
+    /// Whether the ignore files of the root are not considered.
+    skip_root_gitignore: bool,
+    /// The names of the ignore files to look for.
+    ignore_filenames: Vec<String>,
+    /// The rules applied on top of the ignore files, relative to the root.
+    extra_ignore: Option<GitIgnore>,
+    /// The gitignores read so far, until one of their ignore files changes.
+    gitignores: GitIgnoreCache,
+    /// Files written by cunw itself, which must not trigger a rebuild.
+    written_files: HashSet<PathBuf>,
+}
+
+impl ChangeFilter {
+    pub fn new(
+        root: PathBuf,
+        excluded_paths: GlobSet,
+        excluded_dirs: GlobSet,
+        excluded_files: GlobSet,
+        consider_gitignores: bool,
+    ) -> Self {
+        Self {
+            root,
+            excluded_paths,
+            excluded_dirs,
+            excluded_files,
+            included_names: HashSet::new(),
+            consider_gitignores,
+            skip_root_gitignore: false,
+            ignore_filenames: vec![DEFAULT_IGNORE_FILENAME.to_string()],
+            extra_ignore: None,
+            gitignores: GitIgnoreCache::default(),
+            written_files: HashSet::new(),
+        }
+    }
+
+    /// Sets the names of the ignore files to look for, like
+    /// [`CodebaseBuilder::ignore_filenames`](crate::codebase::CodebaseBuilder::ignore_filenames).
+    pub fn ignore_filenames(mut self, ignore_filenames: Vec<String>) -> Self {
+        self.ignore_filenames = ignore_filenames;
+        self
+    }
+
+    /// Sets the rules applied on top of the ignore files, like
+    /// [`CodebaseBuilder::extra_ignore`](crate::codebase::CodebaseBuilder::extra_ignore).
+    pub fn extra_ignore(mut self, extra_ignore: GitIgnore) -> Self {
+        self.extra_ignore = Some(extra_ignore.with_root(self.root.clone()));
+        self
+    }
+
+    /// Sets the names of the files that are included even if they match the exclude
+    /// patterns, like the walk does.
+    pub fn included_names(mut self, included_names: HashSet<String>) -> Self {
+        self.included_names = included_names;
+        self
+    }
+
+    /// Does not consider the ignore files of the root, like the walk does.
+    pub fn skip_root_gitignore(mut self, skip_root_gitignore: bool) -> Self {
+        self.skip_root_gitignore = skip_root_gitignore;
+        self
+    }
+
+    /// Sets the files written by the last build.
+    pub fn set_written_files(&mut self, written_files: Vec<PathBuf>) {
+        self.written_files = written_files
+            .into_iter()
+            .filter_map(|file| file.canonicalize().ok())
+            .collect();
+    }
+
+    /// Returns whether a change of `path` should trigger a rebuild.
+    ///
+    /// A path is ignored if it, or one of its parent directories, is excluded
+    /// or ignored by a gitignore, since the walk would not have entered it. The
+    /// gitignores are read once per directory, and again after their ignore files change.
+    pub fn is_relevant(&mut self, path: &Path) -> bool {
+        if self.written_files.contains(path) {
+            return false;
+        }
+        let Ok(relative_path) = path.strip_prefix(&self.root) else {
+            return false;
+        };
+
+        let mut directory = self.root.clone();
+        let mut gitignores = Vec::new();
+        let mut current = PathBuf::new();
+        let components_len = relative_path.components().count();
+        for (i, component) in relative_path.components().enumerate() {
+            let is_root = directory == self.root;
+            if self.consider_gitignores && !(is_root && self.skip_root_gitignore) {
+                match self
+                    .gitignores
+                    .get(&directory, &self.ignore_filenames, is_root)
+                {
+                    Ok(gitignore) => gitignores.extend(gitignore),
+                    Err(err) => {
+                        Logger::debug(format!("Failed to read gitignore: {}", err).as_str())
+                    }
+                }
+            }
+            current.push(component);
+            directory.push(component);
+            // Only the last component may be a file, unless it is a directory
+            let is_file = i + 1 == components_len && !directory.is_dir();
+            let is_included_name = is_file
+                && self
+                    .included_names
+                    .contains(component.as_os_str().to_string_lossy().as_ref());
+            if !is_included_name {
+                if self.excluded_paths.is_match(&current)
+                    || self.excluded_paths.is_match(&directory)
+                {
+                    return false;
+                }
+                let excluded_by_kind = if is_file {
+                    &self.excluded_files
+                } else {
+                    &self.excluded_dirs
+                };
+                if excluded_by_kind.is_match(&current) || excluded_by_kind.is_match(&directory) {
+                    return false;
+                }
+            }
+            // The nearest gitignores take precedence, and the extra rules apply on top
+            if GitIgnore::is_excluded_by_any(gitignores.iter().rev(), &directory, !is_file)
+                || GitIgnore::is_excluded_by_any(&self.extra_ignore, &directory, !is_file)
+            {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Returns whether an event should trigger a rebuild.
+    fn is_relevant_event(&mut self, event: &Event) -> bool {
+        let is_change = !matches!(
+            event.kind,
+            EventKind::Access(_)
+                | EventKind::Modify(ModifyKind::Metadata(MetadataKind::AccessTime))
+        );
+        if !is_change {
+            return false;
+        }
+        for path in &event.paths {
+            self.gitignores
+                .invalidate(&self.root, path, &self.ignore_filenames);
+        }
+        event.paths.iter().any(|path| self.is_relevant(path))
+    }
+}
+
+/// Runs `regenerate` once, then again each time a relevant file under the root of
+/// `filter` changes, until Ctrl-C is pressed.
+///
+/// `regenerate` returns the files it wrote, so that they do not trigger a rebuild.
+/// Errors of the rebuilds are logged instead of stopping the watch.
+pub async fn watch<F, Fut>(mut filter: ChangeFilter, mut regenerate: F) -> Result<()>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<Vec<PathBuf>>>,
+{
+    filter.set_written_files(regenerate().await?);
+
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    let mut watcher = notify::recommended_watcher(move |event| {
+        // The receiver is only dropped once we stop watching
+        let _ = sender.send(event);
+    })
+    .map_err(|err| CunwError::new(err.into()))?;
+    watcher
+        .watch(&filter.root, RecursiveMode::Recursive)
+        .map_err(|err| CunwError::new(err.into()).with_file(filter.root.clone()))?;
+    Logger::info(
+        format!(
+            "Watching {} for changes, press Ctrl-C to stop",
+            filter.root.display()
+        )
+        .as_str(),
+    );
+
+    let ctrl_c = tokio::signal::ctrl_c();
+    tokio::pin!(ctrl_c);
+    loop {
+        tokio::select! {
+            _ = &mut ctrl_c => break,
+            event = receiver.recv() => {
+                let Some(event) = event else { break };
+                match event {
+                    Ok(event) if filter.is_relevant_event(&event) => {}
+                    Ok(_) => continue,
+                    Err(err) => {
+                        Logger::warn(format!("Error while watching for changes: {}", err).as_str());
+                        continue;
+                    }
+                }
+            }
+        }
+
+        // Wait for the changes to settle
+        loop {
+            tokio::select! {
+                _ = tokio::time::sleep(DEBOUNCE) => break,
+                event = receiver.recv() => if event.is_none() { break },
+            }
+        }
+
+        tokio::select! {
+            _ = &mut ctrl_c => break,
+            result = regenerate() => match result {
+                Ok(written_files) => {
+                    filter.set_written_files(written_files);
+                    Logger::info(
+                        format!("[{}] Regenerated", chrono::Local::now().format("%H:%M:%S")).as_str(),
+                    );
+                }
+                Err(err) => Logger::error(format!("Failed to regenerate: {}", err).as_str()),
+            },
+        }
+    }
+    Logger::info("Stopped watching");
+    Ok(())
+}
+
+/// Canonicalizes the watched directory, like the walk does.
+pub fn watched_root(path: &Path) -> Result<PathBuf> {
+    path.canonicalize().map_err(|err| {
+        CunwError::new(CunwErrorKind::InvalidRoot(path.to_path_buf(), err))
+            .with_file(path.to_path_buf())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gitignore::GitignoreBuilder;
+    use globset::{Glob, GlobSetBuilder};
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn glob_set(patterns: &[&str]) -> GlobSet {
+        let mut builder = GlobSetBuilder::new();
+        for pattern in patterns {
+            builder.add(Glob::new(pattern).unwrap());
+        }
+        builder.build().unwrap()
+    }
+
+    fn filter_for(dir: &TempDir, excluded: &[&str]) -> ChangeFilter {
+        let root = watched_root(dir.path()).unwrap();
+        ChangeFilter::new(
+            root,
+            glob_set(excluded),
+            GlobSet::empty(),
+            GlobSet::empty(),
+            true,
+        )
+    }
+
+    #[test]
+    fn test_change_filter() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir_all(dir.path().join("src")).unwrap();
+        fs::create_dir_all(dir.path().join("target/debug")).unwrap();
+        fs::write(dir.path().join(".gitignore"), "*.swp\n").unwrap();
+        fs::write(dir.path().join("output.txt"), "").unwrap();
+
+        let mut filter = filter_for(&dir, &["**/target"]);
+        filter.set_written_files(vec![dir.path().join("output.txt")]);
+        let root = filter.root.clone();
+
+        assert!(filter.is_relevant(&root.join("src/main.rs")));
+        assert!(!filter.is_relevant(&root.join("src/.main.rs.swp")));
+        assert!(!filter.is_relevant(&root.join("target/debug/cunw")));
+        assert!(!filter.is_relevant(&root.join("output.txt")));
+        assert!(!filter.is_relevant(Path::new("/somewhere/else.rs")));
+    }
+
+    #[test]
+    fn test_change_filter_nested_gitignore() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir_all(dir.path().join("web")).unwrap();
+        fs::write(dir.path().join("web/.gitignore"), "dist/\n").unwrap();
+
+        let mut filter = filter_for(&dir, &[]);
+        let root = filter.root.clone();
+
+        assert!(filter.is_relevant(&root.join("web/index.js")));
+        assert!(!filter.is_relevant(&root.join("web/dist/index.js")));
+    }
+
+    #[test]
+    fn test_change_filter_excluded_dirs_and_files() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir_all(dir.path().join("build")).unwrap();
+        fs::create_dir_all(dir.path().join("src")).unwrap();
+        fs::write(dir.path().join("src/build"), "").unwrap();
+        fs::create_dir_all(dir.path().join("vendor.lock")).unwrap();
+
+        let root = watched_root(dir.path()).unwrap();
+        let mut filter = ChangeFilter::new(
+            root.clone(),
+            GlobSet::empty(),
+            glob_set(&["**/build"]),
+            glob_set(&["**/*.lock"]),
+            true,
+        );
+
+        assert!(!filter.is_relevant(&root.join("build/out.rs")));
+        assert!(!filter.is_relevant(&root.join("Cargo.lock")));
+        // A file named like an excluded directory, and the other way around
+        assert!(filter.is_relevant(&root.join("src/build")));
+        assert!(filter.is_relevant(&root.join("vendor.lock/lib.rs")));
+    }
+
+    #[test]
+    fn test_change_filter_included_names() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir_all(dir.path().join("docker")).unwrap();
+        fs::create_dir_all(dir.path().join("target")).unwrap();
+
+        let mut filter = filter_for(&dir, &["docker/*", "target"])
+            .included_names(HashSet::from(["Dockerfile".to_string()]));
+        let root = filter.root.clone();
+
+        assert!(filter.is_relevant(&root.join("docker/Dockerfile")));
+        assert!(!filter.is_relevant(&root.join("docker/compose.yml")));
+        // The files of an excluded directory are not reached by the walk
+        assert!(!filter.is_relevant(&root.join("target/Dockerfile")));
+    }
+
+    #[test]
+    fn test_change_filter_ignore_filenames_and_extra_ignore() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir_all(dir.path().join("docs")).unwrap();
+        fs::write(dir.path().join(".gitignore"), "*.log\n").unwrap();
+        fs::write(dir.path().join(".cunwignore"), "*.tmp\n").unwrap();
+        let mut builder = GitignoreBuilder::new("/elsewhere");
+        builder.add_line(None, "*.md").unwrap();
+        let extra_ignore = GitIgnore::from_gitignore(builder.build().unwrap());
+
+        let mut filter = filter_for(&dir, &[])
+            .ignore_filenames(vec![".cunwignore".to_string()])
+            .extra_ignore(extra_ignore);
+        let root = filter.root.clone();
+
+        assert!(filter.is_relevant(&root.join("src/main.rs")));
+        assert!(filter.is_relevant(&root.join("debug.log")));
+        assert!(!filter.is_relevant(&root.join("scratch.tmp")));
+        assert!(!filter.is_relevant(&root.join("docs/guide.md")));
+
+        // The extra rules apply even when the ignore files are not considered
+        let mut filter = ChangeFilter::new(
+            root.clone(),
+            GlobSet::empty(),
+            GlobSet::empty(),
+            GlobSet::empty(),
+            false,
+        )
+        .extra_ignore(GitIgnore::from_gitignore(builder.build().unwrap()));
+        assert!(filter.is_relevant(&root.join("scratch.tmp")));
+        assert!(!filter.is_relevant(&root.join("docs/guide.md")));
+    }
+
+    #[test]
+    fn test_change_filter_gitignore_changes() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir_all(dir.path().join("web")).unwrap();
+        fs::write(dir.path().join("web/.gitignore"), "dist/\n").unwrap();
+
+        let mut filter = filter_for(&dir, &[]);
+        let root = filter.root.clone();
+        let event = |path: PathBuf| Event::new(EventKind::Modify(ModifyKind::Any)).add_path(path);
+
+        assert!(!filter.is_relevant_event(&event(root.join("web/dist/index.js"))));
+        assert!(filter.is_relevant_event(&event(root.join("web/build/index.js"))));
+
+        // The cached gitignore is only read again once its file changes
+        fs::write(dir.path().join("web/.gitignore"), "build/\n").unwrap();
+        assert!(filter.is_relevant(&root.join("web/build/index.js")));
+        assert!(filter.is_relevant_event(&event(root.join("web/.gitignore"))));
+        assert!(filter.is_relevant_event(&event(root.join("web/dist/index.js"))));
+        assert!(!filter.is_relevant_event(&event(root.join("web/build/index.js"))));
+
+        // As are the ones of a directory that was moved or removed
+        fs::remove_dir_all(dir.path().join("web")).unwrap();
+        filter.is_relevant_event(&event(root.join("web")));
+        assert!(filter.is_relevant(&root.join("web/build/index.js")));
+    }
+}